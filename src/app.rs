@@ -1,6 +1,17 @@
-use ratatui::widgets::ListState;
-use crate::config::Config;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, EnvValue};
+use crate::fuzzy::{self, Match};
 use crate::i18n::I18nMessages;
+use crate::secret::Secret;
+use crate::state::{self, SessionState, VariableStatus};
+use ratatui::widgets::ListState;
+
+/// How long a revealed value stays on screen.
+const REVEAL_DURATION: Duration = Duration::from_secs(5);
+/// How many of its last characters a value shows when first revealed.
+const REVEAL_TAIL: usize = 4;
 
 // Represents the current phase of user selection.
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -9,6 +20,66 @@ pub enum SelectionPhase {
     Value,
 }
 
+/// The steps of typing a value that isn't in the config.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CustomStage {
+    /// Typing the value.
+    Value,
+    /// Choosing whether to save it to a config file.
+    SaveChoice,
+    /// Typing the label to save it with.
+    Label,
+    /// Finished; the value is set when the TUI exits.
+    Done,
+}
+
+/// A value typed in the TUI instead of chosen from the config.
+#[derive(Debug)]
+pub struct CustomInput {
+    pub value: Secret,
+    /// Whether the value is shown instead of masked.
+    pub revealed: bool,
+    pub stage: CustomStage,
+    /// Where to save the value: `Some(true)` for the global file, `Some(false)` for the local one.
+    pub save_global: Option<bool>,
+    pub label: String,
+}
+
+impl CustomInput {
+    fn new() -> Self {
+        CustomInput {
+            // Reserve room up front so typing doesn't reallocate and leave copies of the value behind.
+            value: Secret::from(String::with_capacity(1024)),
+            revealed: false,
+            stage: CustomStage::Value,
+            save_global: None,
+            label: String::new(),
+        }
+    }
+}
+
+/// What is shown of a value revealed with Ctrl+R.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Revealed<'a> {
+    /// The whole value.
+    Full(&'a str),
+    /// The last few characters of the value.
+    Tail(&'a str),
+    /// The value isn't written inline, so it isn't read.
+    NotInline,
+}
+
+/// A value revealed for a moment.
+#[derive(Debug)]
+struct Reveal {
+    variable: String,
+    /// The index of the value in the variable's values.
+    index: usize,
+    /// Whether the whole value is shown, rather than only its last characters.
+    full: bool,
+    until: Instant,
+}
+
 // Holds the entire state of the application.
 pub struct App<'a> {
     pub should_quit: bool,
@@ -22,6 +93,33 @@ pub struct App<'a> {
     // The variable name selected in the first phase.
     pub selected_variable: Option<String>,
     pub search_query: String,
+    // Whether the selected value is waiting for the user to confirm it.
+    pub confirming: bool,
+    // What gnb-envswap has set in this shell session.
+    pub session: SessionState,
+    // Reads the current value of a variable; the process environment outside of tests.
+    pub env: fn(&str) -> Option<String>,
+    // The value being typed for the selected variable, if the custom value item was chosen.
+    pub custom: Option<CustomInput>,
+    // Whether values may be revealed (`allow_reveal` in the settings).
+    pub allow_reveal: bool,
+    // The value revealed with Ctrl+R, until it is masked again.
+    reveal: Option<Reveal>,
+    // Values chosen with Tab, by variable, as indices into the variable's values.
+    // They are set together with the final selection, or on their own with Ctrl+S.
+    pub pending: BTreeMap<String, usize>,
+    // Whether quitting waits for the user to confirm that the chosen values are dropped.
+    pub discarding: bool,
+    // Whether the search query is also matched against inline values (the `search_values` setting).
+    pub search_values: bool,
+    // Whether a variable with a single value selects it right away (the `auto_select_single` setting).
+    pub auto_select_single: bool,
+    // Whether there is an earlier change to undo.
+    pub can_undo: bool,
+    // Whether the user asked to undo the latest change instead of selecting a value.
+    pub undo_requested: bool,
+    // The (variable, label) pairs the undo would restore that are marked with `confirm`.
+    pub undo_confirmations: Vec<(String, String)>,
 }
 
 impl<'a> App<'a> {
@@ -45,34 +143,409 @@ impl<'a> App<'a> {
             current_phase: SelectionPhase::Variable,
             selected_variable: None,
             search_query: String::new(),
+            confirming: false,
+            session: SessionState::default(),
+            env: |name| std::env::var(name).ok(),
+            custom: None,
+            allow_reveal: true,
+            reveal: None,
+            pending: BTreeMap::new(),
+            discarding: false,
+            search_values: false,
+            auto_select_single: false,
+            can_undo: false,
+            undo_requested: false,
+            undo_confirmations: Vec::new(),
         }
     }
 
-    /// Returns a list of variable names that match the current search query.
+    /// Returns a list of variable names that match the current search query, best match first.
     pub fn filtered_variables(&self) -> Vec<&String> {
+        self.matched_variables()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Returns the variable names that match the current search query, with how they matched.
+    pub fn matched_variables(&self) -> Vec<(&String, Match)> {
+        let mut matches: Vec<_> = self
+            .sorted_variable_names
+            .iter()
+            .filter_map(|name| Some((name, fuzzy::fuzzy_match(&self.search_query, name)?)))
+            .collect();
+        // The sort is stable, so equally good matches stay in alphabetical order.
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        matches
+    }
+
+    /// Returns a list of values for the selected variable that match the current search query, best match first.
+    pub fn filtered_values(&self) -> Vec<&EnvValue> {
+        self.matched_values()
+            .into_iter()
+            .map(|(value, _)| value)
+            .collect()
+    }
+
+    /// Returns the values of the selected variable that match the current search query, with how
+    /// their labels matched.
+    ///
+    /// Labels are matched fuzzily. With `search_values`, a value whose label doesn't match but
+    /// whose inline value contains the query is listed after them, with nothing highlighted.
+    /// Otherwise values are never searched, so typing can't be used to probe a hidden value.
+    pub fn matched_values(&self) -> Vec<(&EnvValue, Match)> {
+        let Some(env_var) = self
+            .selected_variable
+            .as_ref()
+            .and_then(|var_name| self.config.get(var_name))
+        else {
+            return vec![];
+        };
         let query = self.search_query.to_lowercase();
-        self.sorted_variable_names
+        let mut matches: Vec<_> = env_var
+            .values
+            .iter()
+            .filter_map(|v| {
+                if let Some(m) = fuzzy::fuzzy_match(&self.search_query, &v.label) {
+                    return Some((v, m));
+                }
+                let value_matches = self.search_values
+                    && v.value.as_ref().is_some_and(|value| {
+                        Secret::from(value.expose().to_lowercase())
+                            .expose()
+                            .contains(&query)
+                    });
+                value_matches.then(|| {
+                    let m = Match {
+                        score: 0,
+                        positions: Vec::new(),
+                    };
+                    (v, m)
+                })
+            })
+            .collect();
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        matches
+    }
+
+    /// Returns the value currently highlighted in the filtered value list.
+    pub fn selected_value(&self) -> Option<&EnvValue> {
+        let index = self.value_list_state.selected()?;
+        self.filtered_values().get(index).copied()
+    }
+
+    /// Returns the index of the highlighted value in its variable's values.
+    fn selected_index(&self) -> Option<usize> {
+        let value = self.selected_value()?;
+        self.config
+            .get(self.selected_variable.as_ref()?)?
+            .values
+            .iter()
+            .position(|v| std::ptr::eq(v, value))
+    }
+
+    /// Reveals the highlighted value for a few seconds: its last characters at first,
+    /// and the whole value if it is revealed again while still shown.
+    pub fn reveal_selected(&mut self) {
+        if !self.allow_reveal {
+            return;
+        }
+        let (Some(variable), Some(index)) = (self.selected_variable.clone(), self.selected_index())
+        else {
+            return;
+        };
+        let again = self.reveal.as_ref().is_some_and(|r| {
+            r.variable == variable && r.index == index && Instant::now() < r.until
+        });
+        self.reveal = Some(Reveal {
+            variable,
+            index,
+            full: again,
+            until: Instant::now() + REVEAL_DURATION,
+        });
+    }
+
+    /// Returns what to show for `value` while it is revealed, or `None` if it is masked.
+    ///
+    /// Only inline values can be revealed; the others would have to be read from
+    /// a file, run or decrypted, which the TUI never does. The text is borrowed from
+    /// the config rather than copied into another owned `String`.
+    pub fn revealed_text(&self, value: &EnvValue) -> Option<Revealed<'a>> {
+        let reveal = self.reveal.as_ref().filter(|r| Instant::now() < r.until)?;
+        let revealed = self
+            .config
+            .get(&reveal.variable)?
+            .values
+            .get(reveal.index)?;
+        if !self.allow_reveal || !std::ptr::eq(revealed, value) {
+            return None;
+        }
+        let Some(inline) = &revealed.value else {
+            return Some(Revealed::NotInline);
+        };
+        let inline = inline.expose();
+        if reveal.full {
+            return Some(Revealed::Full(inline));
+        }
+        let start = inline
+            .char_indices()
+            .rev()
+            .nth(REVEAL_TAIL - 1)
+            .map_or(0, |(i, _)| i);
+        Some(Revealed::Tail(&inline[start..]))
+    }
+
+    /// Returns the number of entries in the value list: the filtered values and the custom value item after them.
+    pub fn value_item_count(&self) -> usize {
+        self.filtered_values().len() + 1
+    }
+
+    /// Returns whether the custom value item, at the end of the value list, is highlighted.
+    pub fn is_custom_item_selected(&self) -> bool {
+        self.current_phase == SelectionPhase::Value
+            && self.value_list_state.selected() == Some(self.filtered_values().len())
+    }
+
+    /// Starts typing a value for the selected variable.
+    pub fn start_custom_input(&mut self) {
+        self.custom = Some(CustomInput::new());
+    }
+
+    /// Moves the custom value input on to its next step once the current one is filled in.
+    pub fn advance_custom_input(&mut self) {
+        let Some(input) = self.custom.as_mut() else {
+            return;
+        };
+        match input.stage {
+            CustomStage::Value if !input.value.expose().is_empty() => {
+                input.stage = CustomStage::SaveChoice;
+            }
+            CustomStage::Label if !input.label.trim().is_empty() => self.finish_custom_input(),
+            _ => {}
+        }
+    }
+
+    /// Chooses where to save the typed value (`None` to only set it) and moves on.
+    pub fn choose_custom_save(&mut self, global: Option<bool>) {
+        let Some(input) = self.custom.as_mut() else {
+            return;
+        };
+        input.save_global = global;
+        if global.is_some() {
+            input.stage = CustomStage::Label;
+        } else {
+            self.finish_custom_input();
+        }
+    }
+
+    /// Finishes the typed value, asking for confirmation first if a value chosen with Tab needs it.
+    fn finish_custom_input(&mut self) {
+        if let Some(input) = self.custom.as_mut() {
+            input.stage = CustomStage::Done;
+        }
+        if self.selections().iter().any(|(_, value)| value.confirm) {
+            self.confirming = true;
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    /// Returns the typed value once it is finished.
+    pub fn custom_value(&self) -> Option<&CustomInput> {
+        self.custom
+            .as_ref()
+            .filter(|input| input.stage == CustomStage::Done)
+    }
+
+    /// Moves on to choosing a value for `variable`.
+    ///
+    /// With `auto_select_single`, a variable with only one value selects it right away
+    /// (still asking for confirmation if the value needs it). While values are chosen
+    /// with Tab, the value list is shown instead, so that entering a variable doesn't
+    /// set the whole batch by accident.
+    pub fn enter_variable(&mut self, variable: &str) {
+        self.selected_variable = Some(variable.to_string());
+        self.current_phase = SelectionPhase::Value;
+        self.search_query.clear(); // Clear search for the next phase
+        self.select_active_value();
+
+        let single = self
+            .config
+            .get(variable)
+            .is_some_and(|var| var.values.len() == 1);
+        if self.auto_select_single && single && self.pending.is_empty() {
+            self.value_list_state.select(Some(0));
+            self.request_selection();
+        }
+    }
+
+    /// Returns which configured value `variable` currently holds.
+    pub fn variable_status(&self, variable: &str) -> VariableStatus {
+        let current_value = (self.env)(variable);
+        state::variable_status(
+            self.config,
+            &self.session,
+            variable,
+            current_value.as_deref(),
+        )
+    }
+
+    /// Returns the label of the value the selected variable currently holds, if any.
+    pub fn active_label(&self) -> Option<String> {
+        match self.variable_status(self.selected_variable.as_ref()?) {
+            VariableStatus::Label(label) => Some(label),
+            VariableStatus::Custom | VariableStatus::NotSet => None,
+        }
+    }
+
+    /// Highlights the value chosen for the selected variable with Tab, or else the value
+    /// it currently holds, or else the first value.
+    pub fn select_active_value(&mut self) {
+        let pending = self
+            .selected_variable
+            .as_ref()
+            .and_then(|variable| Some((self.config.get(variable)?, *self.pending.get(variable)?)))
+            .and_then(|(var, index)| var.values.get(index));
+        let filtered = self.filtered_values();
+        let index = match pending {
+            Some(pending) => filtered.iter().position(|v| std::ptr::eq(*v, pending)),
+            None => self
+                .active_label()
+                .and_then(|label| filtered.iter().position(|v| v.label == label)),
+        };
+        self.value_list_state.select(Some(index.unwrap_or(0)));
+        self.adjust_selection();
+    }
+
+    /// Returns the values chosen with Tab, as (variable, value) pairs.
+    pub fn pending_values(&self) -> Vec<(&str, &EnvValue)> {
+        self.pending
             .iter()
-            .filter(|name| name.to_lowercase().contains(&query))
+            .filter_map(|(variable, &index)| {
+                let value = self.config.get(variable)?.values.get(index)?;
+                Some((variable.as_str(), value))
+            })
             .collect()
     }
 
-    /// Returns a list of values for the selected variable that match the current search query.
-    pub fn filtered_values(&self) -> Vec<&crate::config::EnvValue> {
-        if let Some(var_name) = &self.selected_variable {
-            if let Some(env_var) = self.config.get(var_name) {
-                let query = self.search_query.to_lowercase();
-                return env_var
-                    .values
-                    .iter()
-                    .filter(|v| {
-                        v.label.to_lowercase().contains(&query)
-                            || v.value.to_lowercase().contains(&query)
-                    })
-                    .collect();
+    /// Returns everything to set: the values chosen with Tab and the highlighted value,
+    /// which replaces any value chosen for the same variable. A typed value replaces it
+    /// too, but isn't included as it isn't in the config.
+    pub fn selections(&self) -> Vec<(&str, &EnvValue)> {
+        let mut selections = self.pending_values();
+        if let Some(variable) = &self.selected_variable {
+            let value = self.selected_value();
+            if value.is_some() || self.custom.is_some() {
+                selections.retain(|(pending, _)| pending != variable);
+            }
+            if let Some(value) = value {
+                selections.push((variable.as_str(), value));
             }
         }
-        vec![]
+        selections
+    }
+
+    /// Chooses the highlighted value with Tab, or unchooses it if it already was,
+    /// and goes back to the variable list to choose more.
+    pub fn toggle_pending(&mut self) {
+        let (Some(variable), Some(index)) = (self.selected_variable.clone(), self.selected_index())
+        else {
+            return;
+        };
+        if self.pending.get(&variable) == Some(&index) {
+            self.pending.remove(&variable);
+        } else {
+            self.pending.insert(variable, index);
+        }
+        self.leave_variable();
+    }
+
+    /// Goes back from the value list to the variable list.
+    pub fn leave_variable(&mut self) {
+        self.current_phase = SelectionPhase::Variable;
+        self.selected_variable = None;
+        self.search_query.clear(); // Clear search when going back
+        self.value_list_state.select(None);
+        self.adjust_selection();
+    }
+
+    /// Selects the highlighted value, together with the values chosen with Tab, asking
+    /// for confirmation first if any of them is marked with `confirm`.
+    pub fn request_selection(&mut self) {
+        if self.selected_value().is_none() {
+            return;
+        }
+        if self.selections().iter().any(|(_, value)| value.confirm) {
+            self.confirming = true;
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    /// Completes a selection that was waiting for confirmation.
+    pub fn confirm_selection(&mut self) {
+        if self.confirming {
+            self.confirming = false;
+            self.should_quit = true;
+        }
+    }
+
+    /// Backs out of the confirmation, returning to the save choice if the value was typed.
+    pub fn cancel_confirmation(&mut self) {
+        self.confirming = false;
+        self.undo_requested = false;
+        if let Some(input) = self.custom.as_mut() {
+            input.stage = CustomStage::SaveChoice;
+        }
+    }
+
+    /// Sets the values chosen with Tab without choosing another one, asking for
+    /// confirmation first if any of them is marked with `confirm`.
+    pub fn apply_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        // From the value list, the highlighted value isn't set along with them.
+        if self.current_phase == SelectionPhase::Value {
+            self.leave_variable();
+        }
+        if self.pending_values().iter().any(|(_, value)| value.confirm) {
+            self.confirming = true;
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    /// Quits without setting anything, first asking whether to drop the values chosen with Tab.
+    pub fn request_quit(&mut self) {
+        if self.pending.is_empty() {
+            self.should_quit = true;
+        } else {
+            self.discarding = true;
+        }
+    }
+
+    /// Drops the values chosen with Tab and quits, once the user confirmed it.
+    pub fn confirm_discard(&mut self) {
+        if self.discarding {
+            self.discarding = false;
+            self.pending.clear();
+            self.should_quit = true;
+        }
+    }
+
+    /// Quits to undo the latest change, if there is one, asking for confirmation first
+    /// if it restores a value marked with `confirm`.
+    pub fn request_undo(&mut self) {
+        if !self.can_undo {
+            return;
+        }
+        self.undo_requested = true;
+        if self.undo_confirmations.is_empty() {
+            self.should_quit = true;
+        } else {
+            self.confirming = true;
+        }
     }
 
     /// Select the next variable in the filtered list, wrapping around.
@@ -115,13 +588,10 @@ impl<'a> App<'a> {
 
     /// Select the next value in the filtered list, wrapping around.
     pub fn next_value(&mut self) {
-        let items = self.filtered_values();
-        if items.is_empty() {
-            return;
-        }
+        let count = self.value_item_count();
         let i = match self.value_list_state.selected() {
             Some(i) => {
-                if i >= items.len() - 1 {
+                if i >= count - 1 {
                     0
                 } else {
                     i + 1
@@ -134,14 +604,11 @@ impl<'a> App<'a> {
 
     /// Select the previous value in the filtered list, wrapping around.
     pub fn previous_value(&mut self) {
-        let items = self.filtered_values();
-        if items.is_empty() {
-            return;
-        }
+        let count = self.value_item_count();
         let i = match self.value_list_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    items.len() - 1
+                    count - 1
                 } else {
                     i - 1
                 }
@@ -167,7 +634,7 @@ impl<'a> App<'a> {
                 }
             }
             SelectionPhase::Value => {
-                let count = self.filtered_values().len();
+                let count = self.value_item_count();
                 if count == 0 {
                     self.value_list_state.select(None);
                 } else if let Some(selected) = self.value_list_state.selected() {
@@ -185,16 +652,23 @@ impl<'a> App<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{EnvVar, EnvValue};
+    use crate::config::{EnvValue, EnvVar};
     use std::collections::HashMap;
 
     #[test]
     fn test_app_initialization() {
         let mut config = HashMap::new();
-        config.insert("VAR1".to_string(), EnvVar {
-            values: vec![EnvValue { label: "L1".to_string(), value: "V1".to_string() }]
-        });
-        
+        config.insert(
+            "VAR1".to_string(),
+            EnvVar {
+                values: vec![EnvValue {
+                    label: "L1".to_string(),
+                    value: Some("V1".into()),
+                    ..Default::default()
+                }],
+            },
+        );
+
         let i18n = crate::i18n::load_messages().unwrap();
         let app = App::new(&config, &i18n);
 
@@ -229,12 +703,23 @@ mod tests {
     #[test]
     fn test_app_filtering_values() {
         let mut config = HashMap::new();
-        config.insert("VAR".to_string(), EnvVar {
-            values: vec![
-                EnvValue { label: "Development".to_string(), value: "dev".to_string() },
-                EnvValue { label: "Production".to_string(), value: "prod".to_string() },
-            ]
-        });
+        config.insert(
+            "VAR".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Development".to_string(),
+                        value: Some("dev".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Production".to_string(),
+                        value: Some("prod".into()),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
         let i18n = crate::i18n::load_messages().unwrap();
         let mut app = App::new(&config, &i18n);
 
@@ -248,6 +733,554 @@ mod tests {
         let filtered = app.filtered_values();
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].label, "Production");
+
+        // The selection index refers to the filtered list, not the full one.
+        app.value_list_state.select(Some(0));
+        assert_eq!(app.selected_value().unwrap().label, "Production");
+    }
+
+    #[test]
+    fn test_app_values_are_only_searched_when_enabled() {
+        let mut config = HashMap::new();
+        config.insert(
+            "VAR".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("sk-live-123".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Skip".to_string(),
+                        value: Some("x".into()),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        app.selected_variable = Some("VAR".to_string());
+
+        app.search_query = "sk-l".to_string();
+        assert!(app.filtered_values().is_empty());
+
+        app.search_values = true;
+        let labels: Vec<_> = app
+            .filtered_values()
+            .iter()
+            .map(|v| v.label.as_str())
+            .collect();
+        assert_eq!(labels, ["Dev"]);
+
+        // Label matches still come before value matches.
+        app.search_query = "sk".to_string();
+        let labels: Vec<_> = app
+            .filtered_values()
+            .iter()
+            .map(|v| v.label.as_str())
+            .collect();
+        assert_eq!(labels, ["Skip", "Dev"]);
+    }
+
+    #[test]
+    fn test_app_multi_select() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("d".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value: Some("p".into()),
+                        confirm: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        config.insert(
+            "DB_HOST".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Local".to_string(),
+                        value: Some("localhost".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Shared".to_string(),
+                        value: Some("db".into()),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        let labels = |selections: Vec<(&str, &EnvValue)>| -> Vec<String> {
+            selections
+                .iter()
+                .map(|(var, v)| format!("{}={}", var, v.label))
+                .collect()
+        };
+
+        // Tab chooses a value and goes back to the variable list.
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(1));
+        app.toggle_pending();
+        assert_eq!(app.current_phase, SelectionPhase::Variable);
+        assert_eq!(labels(app.pending_values()), ["API_KEY=Prod"]);
+
+        // Coming back highlights the chosen value; Tab again unchooses it.
+        app.enter_variable("API_KEY");
+        assert_eq!(app.value_list_state.selected(), Some(1));
+        app.toggle_pending();
+        assert!(app.pending.is_empty());
+
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(1));
+        app.toggle_pending();
+        app.enter_variable("DB_HOST");
+        app.value_list_state.select(Some(1));
+        assert_eq!(labels(app.selections()), ["API_KEY=Prod", "DB_HOST=Shared"]);
+
+        // A chosen value that needs confirmation is confirmed before everything is set.
+        app.request_selection();
+        assert!(app.confirming);
+        app.confirm_selection();
+        assert!(app.should_quit);
+
+        // The final selection replaces a value chosen for the same variable.
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(0));
+        assert_eq!(labels(app.selections()), ["API_KEY=Dev"]);
+    }
+
+    #[test]
+    fn test_app_apply_or_discard_pending() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("d".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value: Some("p".into()),
+                        confirm: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        // Nothing chosen: Ctrl+S does nothing and Esc quits right away.
+        app.apply_pending();
+        assert!(!app.should_quit);
+        app.request_quit();
+        assert!(app.should_quit);
+
+        // The chosen values are set from the variable list, confirmed if needed.
+        let mut app = App::new(&config, &i18n);
+        app.pending.insert("API_KEY".to_string(), 1);
+        app.apply_pending();
+        assert!(app.confirming);
+        app.confirm_selection();
+        assert!(app.should_quit);
+        assert_eq!(app.selections().len(), 1);
+
+        // Quitting asks before dropping them.
+        let mut app = App::new(&config, &i18n);
+        app.pending.insert("API_KEY".to_string(), 0);
+        app.request_quit();
+        assert!(app.discarding);
+        assert!(!app.should_quit);
+        app.discarding = false;
+        assert_eq!(app.pending.len(), 1);
+        app.request_quit();
+        app.confirm_discard();
+        assert!(app.should_quit);
+        assert!(app.pending.is_empty());
+
+        // From the value list, only the chosen values are set.
+        let mut app = App::new(&config, &i18n);
+        app.pending.insert("API_KEY".to_string(), 0);
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(1));
+        app.apply_pending();
+        assert!(app.should_quit);
+        assert_eq!(app.selections().len(), 1);
+        assert_eq!(app.selections()[0].1.label, "Dev");
+    }
+
+    #[test]
+    fn test_app_custom_value() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("d".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value: Some("p".into()),
+                        confirm: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        // The custom value item comes after the values and is reached by wrapping around.
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(0));
+        app.previous_value();
+        assert!(app.is_custom_item_selected());
+        assert!(app.selected_value().is_none());
+        app.next_value();
+        assert_eq!(app.value_list_state.selected(), Some(0));
+
+        // It stays available when the search matches nothing.
+        app.search_query = "zzz".to_string();
+        app.adjust_selection();
+        assert!(app.is_custom_item_selected());
+
+        app.start_custom_input();
+        app.advance_custom_input();
+        assert_eq!(app.custom.as_ref().unwrap().stage, CustomStage::Value);
+        app.custom.as_mut().unwrap().value.push_str("typed");
+        app.advance_custom_input();
+        assert_eq!(app.custom.as_ref().unwrap().stage, CustomStage::SaveChoice);
+        assert!(app.custom_value().is_none());
+
+        // Saving asks for a label first.
+        app.choose_custom_save(Some(false));
+        assert_eq!(app.custom.as_ref().unwrap().stage, CustomStage::Label);
+        app.advance_custom_input();
+        assert!(!app.should_quit);
+        app.custom.as_mut().unwrap().label.push_str("Mine");
+        app.advance_custom_input();
+        assert!(app.should_quit);
+        let input = app.custom_value().unwrap();
+        assert_eq!(
+            (input.value.expose(), input.save_global),
+            ("typed", Some(false))
+        );
+    }
+
+    #[test]
+    fn test_app_custom_value_replaces_chosen_value() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![EnvValue {
+                    label: "Prod".to_string(),
+                    value: Some("p".into()),
+                    confirm: true,
+                    ..Default::default()
+                }],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(0));
+        app.toggle_pending();
+        app.enter_variable("API_KEY");
+        app.next_value();
+        app.start_custom_input();
+        app.custom.as_mut().unwrap().value.push('x');
+        app.advance_custom_input();
+
+        // The value chosen with Tab is replaced, so it no longer needs confirmation.
+        assert!(app.selections().is_empty());
+        app.choose_custom_save(None);
+        assert!(!app.confirming);
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_app_reveal() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("dev-key-1234".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value_file: Some("prod.key".to_string()),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        let values = &config["API_KEY"].values;
+
+        app.enter_variable("API_KEY");
+        app.value_list_state.select(Some(0));
+        assert_eq!(app.revealed_text(&values[0]), None);
+
+        // The last characters first, then the whole value; only the highlighted value is shown.
+        app.reveal_selected();
+        assert_eq!(app.revealed_text(&values[0]), Some(Revealed::Tail("1234")));
+        assert_eq!(app.revealed_text(&values[1]), None);
+        app.reveal_selected();
+        assert_eq!(
+            app.revealed_text(&values[0]),
+            Some(Revealed::Full("dev-key-1234"))
+        );
+
+        // Values that aren't inline are never read.
+        app.value_list_state.select(Some(1));
+        app.reveal_selected();
+        assert_eq!(app.revealed_text(&values[0]), None);
+        assert_eq!(app.revealed_text(&values[1]), Some(Revealed::NotInline));
+
+        // It is masked again after a while.
+        app.reveal.as_mut().unwrap().until = Instant::now();
+        assert_eq!(app.revealed_text(&values[1]), None);
+
+        app.allow_reveal = false;
+        app.value_list_state.select(Some(0));
+        app.reveal_selected();
+        assert_eq!(app.revealed_text(&values[0]), None);
+    }
+
+    #[test]
+    fn test_app_auto_selects_single_values() {
+        let mut config = HashMap::new();
+        config.insert(
+            "ONE".to_string(),
+            EnvVar {
+                values: vec![EnvValue {
+                    label: "Only".to_string(),
+                    value: Some("1".into()),
+                    ..Default::default()
+                }],
+            },
+        );
+        config.insert(
+            "RISKY".to_string(),
+            EnvVar {
+                values: vec![EnvValue {
+                    label: "Prod".to_string(),
+                    value: Some("p".into()),
+                    confirm: true,
+                    ..Default::default()
+                }],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        // Off by default: the value list is shown.
+        app.enter_variable("ONE");
+        assert_eq!(app.current_phase, SelectionPhase::Value);
+        assert!(!app.should_quit);
+
+        app.auto_select_single = true;
+        app.enter_variable("ONE");
+        assert!(app.should_quit);
+        assert_eq!(app.selected_value().unwrap().label, "Only");
+
+        // Values that need confirmation still ask for it.
+        app.should_quit = false;
+        app.enter_variable("RISKY");
+        assert!(app.confirming);
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn test_app_auto_select_waits_while_values_are_chosen() {
+        let mut config = HashMap::new();
+        for (variable, label) in [("ONE", "Only"), ("TWO", "Second")] {
+            config.insert(
+                variable.to_string(),
+                EnvVar {
+                    values: vec![EnvValue {
+                        label: label.to_string(),
+                        value: Some("1".into()),
+                        ..Default::default()
+                    }],
+                },
+            );
+        }
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        app.auto_select_single = true;
+        app.pending.insert("TWO".to_string(), 0);
+
+        // The value list is shown, so the value can be chosen like any other.
+        app.enter_variable("ONE");
+        assert_eq!(app.current_phase, SelectionPhase::Value);
+        assert!(!app.should_quit);
+        assert_eq!(app.selected_value().unwrap().label, "Only");
+        app.toggle_pending();
+        assert_eq!(app.pending.len(), 2);
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn test_app_fuzzy_filtering_is_ranked() {
+        let mut config = HashMap::new();
+        config.insert("API_KEY".to_string(), EnvVar { values: vec![] });
+        config.insert("AMPKEY".to_string(), EnvVar { values: vec![] });
+        config.insert("DB_HOST".to_string(), EnvVar { values: vec![] });
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        // The better match comes first, even though it sorts later alphabetically.
+        app.search_query = "apky".to_string();
+        assert_eq!(app.filtered_variables(), ["API_KEY", "AMPKEY"]);
+        assert_eq!(app.matched_variables()[0].1.positions, [0, 1, 4, 6]);
+
+        app.search_query = "dbh".to_string();
+        assert_eq!(app.filtered_variables(), ["DB_HOST"]);
+
+        app.search_query.clear();
+        assert_eq!(app.filtered_variables(), ["AMPKEY", "API_KEY", "DB_HOST"]);
+    }
+
+    #[test]
+    fn test_app_selection_requires_confirmation() {
+        let mut config = HashMap::new();
+        config.insert(
+            "VAR".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("dev".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value: Some("prod".into()),
+                        confirm: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        app.selected_variable = Some("VAR".to_string());
+
+        app.value_list_state.select(Some(1));
+        app.request_selection();
+        assert!(app.confirming);
+        assert!(!app.should_quit);
+        app.confirm_selection();
+        assert!(!app.confirming);
+        assert!(app.should_quit);
+
+        app.should_quit = false;
+        app.value_list_state.select(Some(0));
+        app.request_selection();
+        assert!(!app.confirming);
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_app_undo_needs_an_earlier_change() {
+        let config = HashMap::new();
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+
+        app.request_undo();
+        assert!(!app.undo_requested);
+        assert!(!app.should_quit);
+
+        app.can_undo = true;
+        app.request_undo();
+        assert!(app.undo_requested);
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_app_undo_requires_confirmation() {
+        let config = HashMap::new();
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        app.can_undo = true;
+        app.undo_confirmations = vec![("API_KEY".to_string(), "Prod".to_string())];
+
+        app.request_undo();
+        assert!(app.confirming);
+        assert!(!app.should_quit);
+        app.cancel_confirmation();
+        assert!(!app.undo_requested);
+
+        app.request_undo();
+        app.confirm_selection();
+        assert!(app.undo_requested);
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_app_preselects_active_value() {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("dev".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value_cmd: Some(vec!["pass".to_string()]),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        let i18n = crate::i18n::load_messages().unwrap();
+        let mut app = App::new(&config, &i18n);
+        app.selected_variable = Some("API_KEY".to_string());
+        app.current_phase = SelectionPhase::Value;
+
+        // Not set: the first value is highlighted.
+        app.env = |_| None;
+        app.select_active_value();
+        assert_eq!(app.value_list_state.selected(), Some(0));
+
+        // Set from a command, which only the session state can tell.
+        app.env = |name| (name == "API_KEY").then(|| "from-command".to_string());
+        app.session
+            .record("API_KEY", "Prod", None, None, "from-command");
+        app.select_active_value();
+        assert_eq!(app.value_list_state.selected(), Some(1));
     }
 
     #[test]
@@ -261,11 +1294,17 @@ mod tests {
 
         app.search_query = "a".to_string(); // APPLE, BANANA
         app.variable_list_state.select(Some(0)); // APPLE
-        
+
         app.next_variable(); // APPLE -> BANANA
-        assert_eq!(app.filtered_variables()[app.variable_list_state.selected().unwrap()], &"BANANA".to_string());
-        
+        assert_eq!(
+            app.filtered_variables()[app.variable_list_state.selected().unwrap()],
+            &"BANANA".to_string()
+        );
+
         app.next_variable(); // BANANA -> APPLE (Loop)
-        assert_eq!(app.filtered_variables()[app.variable_list_state.selected().unwrap()], &"APPLE".to_string());
+        assert_eq!(
+            app.filtered_variables()[app.variable_list_state.selected().unwrap()],
+            &"APPLE".to_string()
+        );
     }
 }
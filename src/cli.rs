@@ -1,11 +1,25 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand, ValueEnum};
 
 /// A CLI tool to quickly switch environment variables in a PowerShell session.
 #[derive(Parser, Debug)] // The main CLI structure
-#[command(version, about, long_about = None, disable_help_subcommand = true)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    disable_help_subcommand = true,
+    args_conflicts_with_subcommands = true
+)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    /// Open the TUI with this variable already selected, to choose one of its values.
+    #[arg(value_name = "VARIABLE")]
+    pub variable: Option<String>,
+    /// Print the command even when stdout is a terminal, where the value would be shown on screen.
+    #[arg(long)]
+    pub force_stdout: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -14,6 +28,34 @@ pub enum Commands {
     Edit(EditArgs),
     /// Show the current status of environment variables.
     Show(ShowArgs),
+    /// Output the command that sets a variable to a labeled value, without the TUI.
+    Set(SetArgs),
+    /// Output the commands that restore the variables changed by the latest change.
+    Undo(UndoArgs),
+    /// Show the changes recorded in the history log (enable it with `history = true` in [settings]).
+    History(HistoryArgs),
+    /// Import the entries of a dotenv file as labeled values.
+    Import(ImportArgs),
+    /// Add a labeled value to a variable, reading the value from a hidden prompt or stdin.
+    Add(AddArgs),
+    /// Remove a variable, or only the values with a given label.
+    Remove(RemoveArgs),
+    /// Rename the label of a variable's values.
+    RenameLabel(RenameLabelArgs),
+    /// Encrypt plaintext values in place, turning `value` into `value_enc`.
+    Encrypt(CryptArgs),
+    /// Decrypt encrypted values in place, turning `value_enc` back into `value`.
+    Decrypt(CryptArgs),
+    /// Re-encrypt every encrypted value to the currently configured recipients.
+    Rekey(RekeyArgs),
+    /// Allow the local configuration file to be used, with its current contents.
+    Allow(AllowArgs),
+    /// Stop allowing the local configuration file to be used.
+    Deny(AllowArgs),
+    /// Make the local and global configuration files private to their owner.
+    FixPermissions,
+    /// Print the PowerShell code defining the `envswap` wrapper, for `$PROFILE`.
+    Init,
 }
 
 #[derive(Parser, Debug)]
@@ -21,6 +63,9 @@ pub struct EditArgs {
     /// The target configuration file to edit.
     #[arg(value_enum, default_value_t = EditTarget::Local)]
     pub target: EditTarget,
+    /// Pre-populate the file with the current values of these variables (comma-separated).
+    #[arg(long, value_delimiter = ',', value_name = "VARS")]
+    pub from_env: Vec<String>,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -35,3 +80,113 @@ pub struct ShowArgs {
     #[arg(long)]
     pub reveal: bool,
 }
+
+#[derive(Parser, Debug)]
+pub struct SetArgs {
+    /// The environment variable name (e.g. API_KEY).
+    pub variable: String,
+    /// The label of the value to set, with or without its <Work>/<Home> prefix.
+    pub label: String,
+    /// Confirm setting a value marked with `confirm = true`.
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Print the command even when stdout is a terminal, where the value would be shown on screen.
+    #[arg(long)]
+    pub force_stdout: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryArgs {
+    /// Only show changes of this variable.
+    #[arg(long, value_name = "VARIABLE")]
+    pub var: Option<String>,
+    /// Print the entries as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct UndoArgs {
+    /// Confirm restoring a value marked with `confirm = true`.
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Print the commands even when stdout is a terminal, where the values would be shown on screen.
+    #[arg(long)]
+    pub force_stdout: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportArgs {
+    /// The dotenv file to import (e.g. .env.dev).
+    pub file: PathBuf,
+    /// The label given to every imported value.
+    #[arg(long)]
+    pub label: String,
+    /// Import into the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct AddArgs {
+    /// The environment variable name (e.g. API_KEY).
+    pub variable: String,
+    /// The label of the new value.
+    #[arg(long)]
+    pub label: String,
+    /// Read the value from stdin instead of prompting for it.
+    #[arg(long)]
+    pub value_stdin: bool,
+    /// Edit the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RemoveArgs {
+    /// The environment variable name.
+    pub variable: String,
+    /// Only remove the values with this label. Removes the whole variable if omitted.
+    pub label: Option<String>,
+    /// Edit the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RenameLabelArgs {
+    /// The environment variable name.
+    pub variable: String,
+    /// The current label.
+    pub old: String,
+    /// The new label.
+    pub new: String,
+    /// Edit the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CryptArgs {
+    /// Only convert the values of this variable. Converts every variable if omitted.
+    pub variable: Option<String>,
+    /// Only convert the values with this label.
+    #[arg(long)]
+    pub label: Option<String>,
+    /// Edit the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RekeyArgs {
+    /// Edit the global configuration file instead of the local one.
+    #[arg(long)]
+    pub global: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct AllowArgs {
+    /// The configuration file. Defaults to the one in the current directory.
+    pub path: Option<PathBuf>,
+}
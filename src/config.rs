@@ -1,12 +1,119 @@
+use crate::permissions::{self, PermissionProblem};
+use crate::secret::Secret;
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::{env, fs};
+use zeroize::Zeroizing;
 
-#[derive(Debug, Deserialize, Clone)]
+/// Config file names searched for in each directory, in order of preference.
+const CONFIG_FILE_NAMES: [&str; 4] = [
+    ".env.swap.toml",
+    ".env.swap.json",
+    ".env.swap.yaml",
+    ".env.swap.yml",
+];
+
+/// The serialization formats a config file can be written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the file extension, defaulting to TOML.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+
+    fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(content).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct EnvValue {
     pub label: String,
-    pub value: String,
+    /// The value itself, written inline in the config file.
+    pub value: Option<Secret>,
+    /// A file containing the value, read only when this value is selected.
+    pub value_file: Option<String>,
+    /// A command (program and arguments) printing the value, run only when this value is selected.
+    pub value_cmd: Option<Vec<String>>,
+    /// The value encrypted with age (ASCII armored), decrypted only when this value is selected.
+    pub value_enc: Option<String>,
+    /// Other variables set together with this value, e.g. an API URL matching an API key.
+    #[serde(default)]
+    pub also: BTreeMap<String, Secret>,
+    /// Whether selecting this value must be confirmed first, e.g. for production credentials.
+    #[serde(default, alias = "danger")]
+    pub confirm: bool,
+    /// Whether `${VAR}` references in `value` and `also` are expanded. Off by default, so
+    /// values containing `$` are set as written.
+    #[serde(default)]
+    pub interpolate: bool,
+    /// The config file this value was loaded from.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl EnvValue {
+    /// Checks that exactly one way of providing the value is set, and that
+    /// `also` does not set the variable itself.
+    fn validate(&self, variable: &str) -> Result<(), String> {
+        if self.also.contains_key(variable) {
+            return Err(format!(
+                "value '{}' sets its own variable in `also`",
+                self.label
+            ));
+        }
+        if let Some(name) = self.also.keys().find(|name| !is_valid_variable_name(name)) {
+            return Err(format!(
+                "value '{}' sets {} in `also`",
+                self.label,
+                invalid_variable_name(name)
+            ));
+        }
+        if self.value_cmd.as_ref().is_some_and(|cmd| cmd.is_empty()) {
+            return Err(format!("value '{}' has an empty `value_cmd`", self.label));
+        }
+        let sources = [
+            self.value.is_some(),
+            self.value_file.is_some(),
+            self.value_cmd.is_some(),
+            self.value_enc.is_some(),
+        ];
+        match sources.iter().filter(|is_set| **is_set).count() {
+            1 => Ok(()),
+            0 => Err(format!(
+                "value '{}' needs one of `value`, `value_file`, `value_cmd` or `value_enc`",
+                self.label
+            )),
+            _ => Err(format!(
+                "value '{}' must set only one of `value`, `value_file`, `value_cmd` or `value_enc`",
+                self.label
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -17,79 +124,352 @@ pub struct EnvVar {
 
 pub type Config = HashMap<String, EnvVar>;
 
+/// Returns whether `name` can be used as a variable name: letters, digits and `_`,
+/// not starting with a digit.
+///
+/// Names are written unquoted into the emitted `$Env:NAME = ...` commands, so
+/// anything else could end the name and run code through `Invoke-Expression`.
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the error for a variable name rejected by [`is_valid_variable_name`].
+pub fn invalid_variable_name(name: &str) -> String {
+    format!(
+        "{:?} is not a valid variable name (use letters, digits and `_`, not starting with a digit)",
+        name
+    )
+}
+
+/// Prefixes added to labels to show which config file a value came from.
+pub const WORK_PREFIX: &str = "<Work> ";
+pub const HOME_PREFIX: &str = "<Home> ";
+
+/// Returns a label as written in its config file, without the `<Work>`/`<Home>` prefix.
+pub fn strip_origin_prefix(label: &str) -> &str {
+    label
+        .strip_prefix(WORK_PREFIX)
+        .or_else(|| label.strip_prefix(HOME_PREFIX))
+        .unwrap_or(label)
+}
+
+/// Finds a value of `variable` by label.
+///
+/// The label may be given with or without its `<Work>`/`<Home>` prefix. Without
+/// a prefix, the work directory's value wins, like everywhere else.
+pub fn find_value<'a>(config: &'a Config, variable: &str, label: &str) -> Option<&'a EnvValue> {
+    let values = &config.get(variable)?.values;
+    values.iter().find(|v| v.label == label).or_else(|| {
+        values
+            .iter()
+            .find(|v| strip_origin_prefix(&v.label) == label)
+    })
+}
+
+/// Tool settings, read from the reserved `[settings]` table.
+///
+/// Settings are only honored in the home directory's config file, so a
+/// project-local config can never change how the tool itself behaves.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// The command `edit` opens config files with (e.g. "code --wait").
+    pub editor: Option<String>,
+    /// The age identity file used to decrypt `value_enc` values.
+    pub age_identity: Option<String>,
+    /// The age recipients new values are encrypted to. Defaults to the identity's own key.
+    pub age_recipients: Option<Vec<String>>,
+    /// Refuse to use config files that other users can access, instead of warning about them.
+    #[serde(default)]
+    pub strict_permissions: bool,
+    /// Also match the TUI's search query against inline values, not only labels.
+    #[serde(default)]
+    pub search_values: bool,
+    /// In the TUI, select the value of a variable that has only one instead of listing it.
+    #[serde(default)]
+    pub auto_select_single: bool,
+    /// Record every change in the history log read by `gnb-envswap history`.
+    #[serde(default)]
+    pub history: bool,
+    /// Whether values may be shown on screen, in the TUI or with `show --reveal`. Defaults to true.
+    pub allow_reveal: Option<bool>,
+}
+
+impl Settings {
+    /// Returns whether values may be shown on screen. Locked-down machines set `allow_reveal = false`.
+    pub fn allows_reveal(&self) -> bool {
+        self.allow_reveal.unwrap_or(true)
+    }
+}
+
+/// The contents of a single config file.
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    settings: Settings,
+    #[serde(flatten)]
+    variables: Config,
+    /// Why other users could read or change this file, if it holds any values.
+    #[serde(skip)]
+    permission_problems: Vec<PermissionProblem>,
+}
+
+/// Only the `[settings]` table of a config file, ignoring everything else in it.
+#[derive(Debug, Deserialize)]
+struct SettingsOnly {
+    #[serde(default)]
+    settings: Settings,
+}
+
+/// Finds a variable named `settings`, which older files may have but is now reserved.
+#[derive(Debug, Deserialize)]
+struct SettingsVariable {
+    settings: Option<SettingsValues>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SettingsValues {
+    values: Option<IgnoredAny>,
+}
+
+/// The merged variables of the work and home config files, plus the settings.
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub variables: Config,
+    pub settings: Settings,
+    /// Config files that other users can access, to be warned about.
+    pub insecure_files: Vec<(PathBuf, PermissionProblem)>,
+}
+
 /// Internal logic for loading and merging configuration from given paths.
+#[cfg(test)]
 fn load_config_from_paths(
     work_path: Option<PathBuf>,
     home_path: Option<PathBuf>,
-) -> Result<Config, String> {
+) -> Result<LoadedConfig, String> {
+    let work = work_path.map(|p| ConfigSource::read(&p)).transpose()?;
+    load_config_from_sources(work.flatten(), home_path)
+}
+
+/// Loads and merges the work config, already read, with the home config at `home_path`.
+fn load_config_from_sources(
+    work: Option<ConfigSource>,
+    home_path: Option<PathBuf>,
+) -> Result<LoadedConfig, String> {
+    let work_path = work.as_ref().map(|source| source.path.clone());
     // If work and home paths are the same, treat as if there's only a work path.
-    let (work_path, home_path) = if work_path == home_path {
-        (work_path, None)
-    } else {
-        (work_path, home_path)
+    let work_is_home = work_path.is_some() && work_path == home_path;
+    let home_path = if work_is_home { None } else { home_path };
+
+    let work_file = work.as_ref().map(ConfigSource::parse).transpose()?;
+    let home_file = read_config_from_path(home_path.as_ref())?;
+
+    let settings = match (&work_file, &home_file) {
+        (Some(work), _) if work_is_home => work.settings.clone(),
+        (_, Some(home)) => home.settings.clone(),
+        _ => Settings::default(),
     };
 
-    let mut work_config = read_config_from_path(work_path.as_ref())?;
-    let mut home_config = read_config_from_path(home_path.as_ref())?;
+    let mut insecure_files = Vec::new();
+    for (path, file) in [(&work_path, &work_file), (&home_path, &home_file)] {
+        if let (Some(path), Some(file)) = (path, file) {
+            for problem in &file.permission_problems {
+                if settings.strict_permissions {
+                    return Err(format!(
+                        "Refusing to use {:?}: it {}. Run `gnb-envswap fix-permissions` to fix it.",
+                        path,
+                        problem.describe()
+                    ));
+                }
+                insecure_files.push((path.clone(), *problem));
+            }
+        }
+    }
+    let mut work_config = work_file.map(|f| f.variables);
+    let mut home_config = home_file.map(|f| f.variables);
 
     // Prepend prefixes to labels to indicate source.
     if let Some(work) = work_config.as_mut() {
         for var in work.values_mut() {
             for val in var.values.iter_mut() {
-                val.label = format!("<Work> {}", val.label);
+                val.label = format!("{}{}", WORK_PREFIX, val.label);
             }
         }
     }
     if let Some(home) = home_config.as_mut() {
         for var in home.values_mut() {
             for val in var.values.iter_mut() {
-                val.label = format!("<Home> {}", val.label);
+                val.label = format!("{}{}", HOME_PREFIX, val.label);
             }
         }
     }
 
-    match (work_config, home_config) {
+    let variables = match (work_config, home_config) {
         (Some(mut work), Some(home)) => {
             // Merge home config into work config.
             for (key, home_var) in home {
-                let work_var = work.entry(key).or_insert_with(|| EnvVar {
-                    values: Vec::new(),
-                });
+                let work_var = work
+                    .entry(key)
+                    .or_insert_with(|| EnvVar { values: Vec::new() });
                 work_var.values.extend(home_var.values);
             }
-            Ok(work)
+            work
         }
-        (Some(work), None) => Ok(work),
-        (None, Some(home)) => Ok(home),
+        (Some(work), None) => work,
+        (None, Some(home)) => home,
         (None, None) => {
-            Err("No .env.swap.toml file found in current or home directory.".to_string())
+            return Err("No .env.swap.toml file found in current or home directory.".to_string());
         }
-    }
+    };
+
+    Ok(LoadedConfig {
+        variables,
+        settings,
+        insecure_files,
+    })
+}
+
+/// Returns the first config file found in `dir`, if any.
+fn find_config_file(dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// Returns the config file in `dir`, or the path a new `.env.swap.toml` would have.
+pub fn config_file_in(dir: &Path) -> PathBuf {
+    find_config_file(dir).unwrap_or_else(|| dir.join(CONFIG_FILE_NAMES[0]))
 }
 
 /// Loads and merges configuration from home and current directories.
 /// Work directory's config takes precedence.
-pub fn load_config() -> Result<Config, String> {
-    let home_path = dirs::home_dir().map(|p| p.join(".env.swap.toml"));
-    let work_path = env::current_dir().ok().map(|p| p.join(".env.swap.toml"));
+///
+/// The work directory's config is skipped unless `allow_work` returns true for it.
+/// It is not asked about when the work directory is the home directory.
+pub fn load_config<F>(allow_work: F) -> Result<LoadedConfig, String>
+where
+    F: FnOnce(&Path, &[u8]) -> bool,
+{
+    let home_path = dirs::home_dir().and_then(|p| find_config_file(&p));
+    let work_path = env::current_dir().ok().and_then(|p| find_config_file(&p));
+    // The work config is read once: the bytes `allow_work` approves are the bytes that
+    // get parsed, so the file can't be swapped for another one in between.
+    let work = work_path
+        .map(|p| ConfigSource::read(&p))
+        .transpose()?
+        .flatten()
+        .filter(|source| {
+            Some(&source.path) == home_path.as_ref() || allow_work(&source.path, &source.content)
+        });
+
+    load_config_from_sources(work, home_path)
+}
+
+/// A config file and the bytes read from it.
+struct ConfigSource {
+    path: PathBuf,
+    content: Zeroizing<Vec<u8>>,
+}
+
+impl ConfigSource {
+    /// Reads the config file at `path`, or returns `None` if it doesn't exist.
+    fn read(path: &Path) -> Result<Option<Self>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read(path)
+            .map(Zeroizing::new)
+            .map_err(|e| format!("Failed to read config file at {:?}: {}", path, e))?;
+        Ok(Some(ConfigSource {
+            path: path.to_path_buf(),
+            content,
+        }))
+    }
+
+    /// Parses the contents that were read.
+    /// The format (TOML, JSON or YAML) is detected from the file extension.
+    fn parse(&self) -> Result<ConfigFile, String> {
+        let p = &self.path;
+        let content = std::str::from_utf8(&self.content)
+            .map_err(|e| format!("Failed to read config file at {:?}: {}", p, e))?;
+        let format = ConfigFormat::from_path(p);
+        let mut config: ConfigFile = format
+            .parse(content)
+            .map_err(|e| format!("Failed to parse {} at {:?}: {}", format.name(), p, e))?;
+        let settings_variable: SettingsVariable = format.parse(content)?;
+        if settings_variable
+            .settings
+            .is_some_and(|s| s.values.is_some())
+        {
+            return Err(format!(
+                "Invalid config at {:?}: `settings` is reserved for tool settings and can no longer be used as a variable name. Rename the variable (for example to `SETTINGS`).",
+                p
+            ));
+        }
 
-    load_config_from_paths(work_path, home_path)
+        for (name, var) in config.variables.iter_mut() {
+            if !is_valid_variable_name(name) {
+                return Err(format!(
+                    "Invalid config at {:?}: {}",
+                    p,
+                    invalid_variable_name(name)
+                ));
+            }
+            for value in var.values.iter_mut() {
+                value
+                    .validate(name)
+                    .map_err(|e| format!("Invalid config at {:?}: {}: {}", p, name, e))?;
+                value.source = Some(p.clone());
+            }
+        }
+        if config.variables.values().any(|var| !var.values.is_empty()) {
+            config.permission_problems = permissions::check(p)?;
+        }
+        Ok(config)
+    }
 }
 
 /// Reads and parses a config file from a given optional path.
-fn read_config_from_path(path: Option<&PathBuf>) -> Result<Option<Config>, String> {
+fn read_config_from_path(path: Option<&PathBuf>) -> Result<Option<ConfigFile>, String> {
     match path {
-        Some(p) if p.exists() => {
-            let content = fs::read_to_string(p)
-                .map_err(|e| format!("Failed to read config file at {:?}: {}", p, e))?;
-            let config: Config = toml::from_str(&content)
-                .map_err(|e| format!("Failed to parse TOML at {:?}: {}", p, e))?;
-            Ok(Some(config))
-        }
-        _ => Ok(None),
+        Some(p) => ConfigSource::read(p)?
+            .map(|source| source.parse())
+            .transpose(),
+        None => Ok(None),
     }
 }
 
+/// Reads only the settings of the home directory's config file.
+///
+/// Errors elsewhere in the file are ignored, so the settings (such as the
+/// editor used to fix those errors) are still available.
+pub fn read_home_settings() -> Result<Settings, String> {
+    let Some(path) = dirs::home_dir().and_then(|p| find_config_file(&p)) else {
+        return Ok(Settings::default());
+    };
+    read_settings_from_path(&path)
+}
+
+fn read_settings_from_path(path: &Path) -> Result<Settings, String> {
+    let content = fs::read_to_string(path)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Failed to read config file at {:?}: {}", path, e))?;
+    let format = ConfigFormat::from_path(path);
+    format
+        .parse::<SettingsOnly>(&content)
+        .map(|file| file.settings)
+        .map_err(|e| format!("Failed to parse {} at {:?}: {}", format.name(), path, e))
+}
+
+/// Checks that the config file at `path` can be read and parsed.
+pub fn check_config_file(path: &Path) -> Result<(), String> {
+    read_config_from_path(Some(&path.to_path_buf())).map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,7 +496,9 @@ mod tests {
         "#;
         fs::write(&work_path, config_content).unwrap();
 
-        let config = load_config_from_paths(Some(work_path), None).unwrap();
+        let config = load_config_from_paths(Some(work_path), None)
+            .unwrap()
+            .variables;
         assert!(config.contains_key("API_KEY"));
         assert_eq!(config["API_KEY"].values.len(), 1);
         assert_eq!(config["API_KEY"].values[0].label, "<Work> Dev");
@@ -150,7 +532,9 @@ mod tests {
         fs::write(&home_path, home_content).unwrap();
         fs::write(&work_path, work_content).unwrap();
 
-        let config = load_config_from_paths(Some(work_path), Some(home_path)).unwrap();
+        let config = load_config_from_paths(Some(work_path), Some(home_path))
+            .unwrap()
+            .variables;
 
         // API_KEY should have two values, work's coming first
         assert_eq!(config["API_KEY"].values.len(), 2);
@@ -162,4 +546,447 @@ mod tests {
         assert_eq!(config["DB_HOST"].values.len(), 1);
         assert_eq!(config["DB_HOST"].values[0].label, "<Home> Home DB");
     }
+
+    #[test]
+    fn test_settings_are_only_read_from_home() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let work_dir = tempfile::tempdir().unwrap();
+        let home_path = home_dir.path().join(".env.swap.toml");
+        let work_path = work_dir.path().join(".env.swap.toml");
+
+        let home_content = r#"
+            [settings]
+            editor = "vim"
+
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Home"
+            value = "home-key"
+        "#;
+        let work_content = r#"
+            [settings]
+            editor = "evil-command"
+
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Work"
+            value = "work-key"
+        "#;
+        fs::write(&home_path, home_content).unwrap();
+        fs::write(&work_path, work_content).unwrap();
+
+        let loaded =
+            load_config_from_paths(Some(work_path.clone()), Some(home_path.clone())).unwrap();
+        assert_eq!(loaded.settings.editor.as_deref(), Some("vim"));
+        assert!(!loaded.variables.contains_key("settings"));
+        assert_eq!(loaded.variables["API_KEY"].values.len(), 2);
+
+        let loaded = load_config_from_paths(Some(work_path), None).unwrap();
+        assert_eq!(loaded.settings.editor, None);
+
+        // Running from the home directory itself still honors its settings.
+        let loaded = load_config_from_paths(Some(home_path.clone()), Some(home_path)).unwrap();
+        assert_eq!(loaded.settings.editor.as_deref(), Some("vim"));
+    }
+
+    #[test]
+    fn test_load_config_parses_the_bytes_that_were_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        fs::write(
+            &path,
+            "[API_KEY]\nvalues = [{ label = \"Dev\", value = \"k\" }]\n",
+        )
+        .unwrap();
+        let source = ConfigSource::read(&path).unwrap().unwrap();
+
+        // Swapped after it was read (and checked against the allowlist).
+        fs::write(
+            &path,
+            "[EVIL]\nvalues = [{ label = \"X\", value_cmd = [\"sh\"] }]\n",
+        )
+        .unwrap();
+        let loaded = load_config_from_sources(Some(source), None).unwrap();
+        assert!(loaded.variables.contains_key("API_KEY"));
+        assert!(!loaded.variables.contains_key("EVIL"));
+    }
+
+    #[test]
+    fn test_invalid_variable_name_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        fs::write(
+            &path,
+            r#"
+            ["A;Start-Process(calc)#"]
+            [["A;Start-Process(calc)#".values]]
+            label = "Dev"
+            value = "x"
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("is not a valid variable name"), "{}", err);
+
+        assert!(is_valid_variable_name("API_KEY"));
+        assert!(is_valid_variable_name("_x1"));
+        assert!(!is_valid_variable_name(""));
+        assert!(!is_valid_variable_name("1X"));
+        assert!(!is_valid_variable_name("X-Y"));
+    }
+
+    #[test]
+    fn test_settings_variable_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        // A variable named `settings`, from before the name was reserved.
+        fs::write(
+            &path,
+            "[settings]\n[[settings.values]]\nlabel = \"Dev\"\nvalue = \"x\"\n",
+        )
+        .unwrap();
+
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("`settings` is reserved"));
+    }
+
+    #[test]
+    fn test_settings_are_read_despite_errors_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        fs::write(
+            &path,
+            "[settings]\neditor = \"vim\"\n\n[API_KEY]\n[[API_KEY.values]]\nlabell = \"Dev\"\n",
+        )
+        .unwrap();
+
+        assert!(read_config_from_path(Some(&path)).is_err());
+        let settings = read_settings_from_path(&path).unwrap();
+        assert_eq!(settings.editor.as_deref(), Some("vim"));
+
+        fs::write(&path, "[settings\neditor = \"vim\"\n").unwrap();
+        assert!(read_settings_from_path(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_insecure_permissions_warn_or_refuse() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home_dir = tempfile::tempdir().unwrap();
+        let work_dir = tempfile::tempdir().unwrap();
+        let home_path = home_dir.path().join(".env.swap.toml");
+        let work_path = work_dir.path().join(".env.swap.toml");
+        fs::write(&home_path, "[settings]\n").unwrap();
+        fs::write(
+            &work_path,
+            "[API_KEY]\nvalues = [{ label = \"Dev\", value = \"k\" }]\n",
+        )
+        .unwrap();
+        fs::set_permissions(&home_path, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::set_permissions(&work_path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        // The home config holds no values, so only the work config is reported.
+        let loaded =
+            load_config_from_paths(Some(work_path.clone()), Some(home_path.clone())).unwrap();
+        assert_eq!(
+            loaded.insecure_files,
+            [(work_path.clone(), PermissionProblem::TooOpen(0o640))]
+        );
+
+        fs::write(&home_path, "[settings]\nstrict_permissions = true\n").unwrap();
+        let err =
+            load_config_from_paths(Some(work_path.clone()), Some(home_path.clone())).unwrap_err();
+        assert!(err.starts_with("Refusing to use"));
+
+        fs::set_permissions(&work_path, fs::Permissions::from_mode(0o600)).unwrap();
+        let loaded = load_config_from_paths(Some(work_path), Some(home_path)).unwrap();
+        assert!(loaded.insecure_files.is_empty());
+    }
+
+    #[test]
+    fn test_read_config_json_and_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join(".env.swap.json");
+        let yaml_path = dir.path().join(".env.swap.yaml");
+        fs::write(
+            &json_path,
+            r#"{ "API_KEY": { "values": [ { "label": "Dev", "value": "dev-key" } ] } }"#,
+        )
+        .unwrap();
+        fs::write(
+            &yaml_path,
+            "API_KEY:\n  values:\n    - label: Dev\n      value: dev-key\n",
+        )
+        .unwrap();
+
+        for path in [json_path, yaml_path] {
+            let config = read_config_from_path(Some(&path))
+                .unwrap()
+                .unwrap()
+                .variables;
+            assert_eq!(config["API_KEY"].values.len(), 1);
+            assert_eq!(config["API_KEY"].values[0].label, "Dev");
+            assert_eq!(
+                config["API_KEY"].values[0]
+                    .value
+                    .as_ref()
+                    .map(Secret::expose),
+                Some("dev-key")
+            );
+            assert_eq!(config["API_KEY"].values[0].source.as_ref(), Some(&path));
+        }
+    }
+
+    #[test]
+    fn test_read_config_unquoted_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.yaml");
+        fs::write(
+            &path,
+            "DB_PORT:\n  values:\n    - label: Local\n      value: 5432\n      also:\n        DB_SSL: false\n",
+        )
+        .unwrap();
+        let config = read_config_from_path(Some(&path))
+            .unwrap()
+            .unwrap()
+            .variables;
+        let value = &config["DB_PORT"].values[0];
+        assert_eq!(value.value.as_ref().map(Secret::expose), Some("5432"));
+        assert_eq!(value.also["DB_SSL"].expose(), "false");
+
+        // Floats would lose their written form (`1.10` is 1.1), so they must be quoted.
+        fs::write(
+            &path,
+            "VERSION:\n  values:\n    - label: Old\n      value: 1.10\n",
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("quote other values"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_config_reports_format_in_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.yml");
+        fs::write(&path, "API_KEY: [not, a, table]\n").unwrap();
+
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.starts_with("Failed to parse YAML"));
+    }
+
+    #[test]
+    fn test_read_config_requires_exactly_one_value_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+
+        fs::write(
+            &path,
+            r#"
+            [CERT]
+            [[CERT.values]]
+            label = "Prod"
+            value_file = "certs/prod.pem"
+        "#,
+        )
+        .unwrap();
+        let config = read_config_from_path(Some(&path))
+            .unwrap()
+            .unwrap()
+            .variables;
+        assert_eq!(
+            config["CERT"].values[0].value_file.as_deref(),
+            Some("certs/prod.pem")
+        );
+
+        fs::write(
+            &path,
+            r#"
+            [CERT]
+            [[CERT.values]]
+            label = "Prod"
+            value = "inline"
+            value_file = "certs/prod.pem"
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("CERT: value 'Prod' must set only one of"));
+
+        fs::write(
+            &path,
+            r#"
+            [CERT]
+            [[CERT.values]]
+            label = "Prod"
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("CERT: value 'Prod' needs one of"));
+
+        fs::write(
+            &path,
+            r#"
+            [CERT]
+            [[CERT.values]]
+            label = "Prod"
+            value_cmd = []
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("CERT: value 'Prod' has an empty `value_cmd`"));
+    }
+
+    #[test]
+    fn test_read_config_also() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+
+        fs::write(
+            &path,
+            r#"
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Prod"
+            value = "prod-key"
+            also = { API_URL = "https://api.prod", REGION = "eu" }
+        "#,
+        )
+        .unwrap();
+        let config = read_config_from_path(Some(&path))
+            .unwrap()
+            .unwrap()
+            .variables;
+        let also = &config["API_KEY"].values[0].also;
+        assert_eq!(also.len(), 2);
+        assert_eq!(also["API_URL"].expose(), "https://api.prod");
+        assert_eq!(also["REGION"].expose(), "eu");
+        // References are only expanded in values that opt in.
+        assert!(!config["API_KEY"].values[0].interpolate);
+
+        fs::write(
+            &path,
+            r#"
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Prod"
+            value = "prod-key"
+            also = { API_KEY = "other" }
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("API_KEY: value 'Prod' sets its own variable in `also`"));
+
+        fs::write(
+            &path,
+            r#"
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Prod"
+            value = "prod-key"
+            also = { "X;evil" = "other" }
+        "#,
+        )
+        .unwrap();
+        let err = read_config_from_path(Some(&path)).unwrap_err();
+        assert!(err.contains("is not a valid variable name"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_config_confirm_and_danger() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+
+        fs::write(
+            &path,
+            r#"
+            [API_KEY]
+            values = [
+                { label = "Dev", value = "dev-key" },
+                { label = "Prod", value = "prod-key", confirm = true },
+                { label = "Admin", value = "admin-key", danger = true },
+            ]
+        "#,
+        )
+        .unwrap();
+        let config = read_config_from_path(Some(&path))
+            .unwrap()
+            .unwrap()
+            .variables;
+        let confirm: Vec<bool> = config["API_KEY"].values.iter().map(|v| v.confirm).collect();
+        assert_eq!(confirm, [false, true, true]);
+    }
+
+    #[test]
+    fn test_find_value_by_label() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let work_dir = tempfile::tempdir().unwrap();
+        let home_path = home_dir.path().join(".env.swap.toml");
+        let work_path = work_dir.path().join(".env.swap.toml");
+        let content = r#"
+            [API_KEY]
+            [[API_KEY.values]]
+            label = "Dev"
+            value = "{}"
+        "#;
+        fs::write(&home_path, content.replace("{}", "home-key")).unwrap();
+        fs::write(&work_path, content.replace("{}", "work-key")).unwrap();
+        let config = load_config_from_paths(Some(work_path), Some(home_path))
+            .unwrap()
+            .variables;
+
+        let found = find_value(&config, "API_KEY", "Dev").unwrap();
+        assert_eq!(found.value.as_ref().map(Secret::expose), Some("work-key"));
+        let found = find_value(&config, "API_KEY", "<Home> Dev").unwrap();
+        assert_eq!(found.value.as_ref().map(Secret::expose), Some("home-key"));
+        assert!(find_value(&config, "API_KEY", "Prod").is_none());
+        assert!(find_value(&config, "MISSING", "Dev").is_none());
+    }
+
+    #[test]
+    fn test_find_config_file_prefers_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_config_file(dir.path()), None);
+
+        fs::write(dir.path().join(".env.swap.yaml"), "").unwrap();
+        assert_eq!(
+            find_config_file(dir.path()),
+            Some(dir.path().join(".env.swap.yaml"))
+        );
+
+        fs::write(dir.path().join(".env.swap.toml"), "").unwrap();
+        assert_eq!(
+            find_config_file(dir.path()),
+            Some(dir.path().join(".env.swap.toml"))
+        );
+    }
+
+    #[test]
+    fn test_load_config_merge_mixed_formats() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let work_dir = tempfile::tempdir().unwrap();
+        let home_path = home_dir.path().join(".env.swap.yaml");
+        let work_path = work_dir.path().join(".env.swap.json");
+
+        fs::write(
+            &home_path,
+            "API_KEY:\n  values:\n    - label: Home\n      value: home-key\n",
+        )
+        .unwrap();
+        fs::write(
+            &work_path,
+            r#"{ "API_KEY": { "values": [ { "label": "Work", "value": "work-key" } ] } }"#,
+        )
+        .unwrap();
+
+        let config = load_config_from_paths(Some(work_path), Some(home_path))
+            .unwrap()
+            .variables;
+        assert_eq!(config["API_KEY"].values.len(), 2);
+        assert_eq!(config["API_KEY"].values[0].label, "<Work> Work");
+        assert_eq!(config["API_KEY"].values[1].label, "<Home> Home");
+    }
 }
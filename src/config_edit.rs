@@ -0,0 +1,544 @@
+//! Format-preserving edits to `.env.swap.toml` files.
+//!
+//! Edits go through `toml_edit` so that comments, ordering and whitespace
+//! written by the user survive untouched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{ArrayOfTables, DocumentMut, InlineTable, Item, Table, TableLike, Value};
+use zeroize::Zeroizing;
+
+use crate::permissions;
+
+/// An editable `.env.swap.toml` document bound to its path on disk.
+pub struct ConfigDocument {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Opens the document at `path`, starting from an empty one if the file doesn't exist.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+            return Err(format!(
+                "Only TOML config files can be edited from the command line: {:?}",
+                path
+            ));
+        }
+        let content = if path.exists() {
+            fs::read_to_string(path)
+                .map(Zeroizing::new)
+                .map_err(|e| format!("Failed to read config file at {:?}: {}", path, e))?
+        } else {
+            Zeroizing::default()
+        };
+        let doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| format!("Failed to parse TOML at {:?}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            doc,
+        })
+    }
+
+    /// Returns the path this document is read from and saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the document back to its path.
+    pub fn save(&self) -> Result<(), String> {
+        let content = Zeroizing::new(self.doc.to_string());
+        permissions::write_private(&self.path, content.as_bytes())
+            .map_err(|e| format!("Failed to write config file at {:?}: {}", self.path, e))
+    }
+
+    /// Appends a labeled value to `variable`, creating the variable if needed.
+    ///
+    /// Returns `Ok(false)` without changing anything if an entry with the same
+    /// label and value already exists.
+    pub fn add_value(&mut self, variable: &str, label: &str, value: &str) -> Result<bool, String> {
+        if !crate::config::is_valid_variable_name(variable) {
+            return Err(crate::config::invalid_variable_name(variable));
+        }
+        if !self.doc.contains_key(variable) {
+            self.insert_variable_table(variable);
+        }
+        let var_table = self.doc[variable]
+            .as_table_like_mut()
+            .ok_or_else(|| format!("'{}' is not a table", variable))?;
+
+        let values = var_table
+            .entry("values")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()));
+
+        match values {
+            Item::ArrayOfTables(entries) => {
+                if entries.iter().any(|t| is_same_entry(t, label, value)) {
+                    return Ok(false);
+                }
+                let mut entry = Table::new();
+                if entries.is_empty() {
+                    // Put the first `[[VAR.values]]` right below its `[VAR]` header.
+                    entry.decor_mut().set_prefix("");
+                }
+                entry["label"] = toml_edit::value(label);
+                entry["value"] = toml_edit::value(value);
+                entries.push(entry);
+            }
+            Item::Value(Value::Array(entries)) => {
+                let exists = entries
+                    .iter()
+                    .filter_map(|v| v.as_inline_table())
+                    .any(|t| is_same_entry(t, label, value));
+                if exists {
+                    return Ok(false);
+                }
+                let mut entry = InlineTable::new();
+                entry.insert("label", label.into());
+                entry.insert("value", value.into());
+                entries.push(entry);
+            }
+            _ => return Err(format!("'{}.values' is not an array of tables", variable)),
+        }
+        Ok(true)
+    }
+
+    /// Appends an empty `[variable]` table at the end of the document.
+    fn insert_variable_table(&mut self, variable: &str) {
+        // Comments at the end of the file (such as the starter template written by
+        // `edit`) belong to the document, not to a table. Move them above the new
+        // table so they keep their place instead of ending up below it.
+        let trailing = self.doc.trailing().as_str().unwrap_or_default().to_string();
+        self.doc.set_trailing("");
+
+        let separator = if self.doc.is_empty() && trailing.is_empty() {
+            ""
+        } else {
+            "\n"
+        };
+        let mut table = Table::new();
+        table
+            .decor_mut()
+            .set_prefix(format!("{}{}", trailing, separator));
+        self.doc.insert(variable, Item::Table(table));
+    }
+
+    /// Removes `variable` and all of its values. Returns false if it didn't exist.
+    pub fn remove_variable(&mut self, variable: &str) -> bool {
+        self.doc.remove(variable).is_some()
+    }
+
+    /// Removes every value of `variable` labeled `label` and returns how many were removed.
+    ///
+    /// The variable itself is removed once its last value is gone.
+    pub fn remove_value(&mut self, variable: &str, label: &str) -> Result<usize, String> {
+        let Some(values) = self.values_mut(variable)? else {
+            return Ok(0);
+        };
+        let has_label = |t: &dyn TableLike| t.get("label").and_then(|l| l.as_str()) == Some(label);
+
+        let (before, after) = match values {
+            Item::ArrayOfTables(entries) => {
+                let before = entries.len();
+                entries.retain(|t| !has_label(t));
+                (before, entries.len())
+            }
+            Item::Value(Value::Array(entries)) => {
+                let before = entries.len();
+                entries.retain(|v| !v.as_inline_table().is_some_and(|t| has_label(t)));
+                (before, entries.len())
+            }
+            _ => unreachable!("values_mut only returns arrays"),
+        };
+
+        if after == 0 && before > 0 {
+            self.doc.remove(variable);
+        }
+        Ok(before - after)
+    }
+
+    /// Renames every value of `variable` labeled `old` to `new` and returns how many were renamed.
+    pub fn rename_label(&mut self, variable: &str, old: &str, new: &str) -> Result<usize, String> {
+        let mut renamed = 0;
+        self.for_each_entry(variable, |entry| {
+            if entry.get("label").and_then(|l| l.as_str()) == Some(old) {
+                set_string(entry, "label", new);
+                renamed += 1;
+            }
+            Ok(())
+        })?;
+        Ok(renamed)
+    }
+
+    /// Replaces the `from` key of matching entries with a `to` key holding `convert(old value)`.
+    ///
+    /// Entries are matched by `variable` and `label` when given; entries without a
+    /// `from` key are skipped. Returns how many entries were converted.
+    pub fn convert_values<F>(
+        &mut self,
+        variable: Option<&str>,
+        label: Option<&str>,
+        from: &str,
+        to: &str,
+        mut convert: F,
+    ) -> Result<usize, String>
+    where
+        F: FnMut(&str) -> Result<String, String>,
+    {
+        let variables: Vec<String> = match variable {
+            Some(variable) => vec![variable.to_string()],
+            None => self
+                .doc
+                .iter()
+                .filter(|(key, item)| *key != "settings" && item.is_table_like())
+                .map(|(key, _)| key.to_string())
+                .collect(),
+        };
+
+        let mut converted = 0;
+        for variable in &variables {
+            self.for_each_entry(variable, |entry| {
+                let label_matches = label
+                    .is_none_or(|label| entry.get("label").and_then(|l| l.as_str()) == Some(label));
+                let Some(old) = entry.get(from).and_then(|v| v.as_str()) else {
+                    return Ok(());
+                };
+                if !label_matches {
+                    return Ok(());
+                }
+
+                let new = convert(old)?;
+                let decor = entry
+                    .get(from)
+                    .and_then(|item| item.as_value())
+                    .map(|v| v.decor().clone());
+                entry.remove(from);
+                set_string(entry, to, &new);
+                if let Some(decor) = decor
+                    && let Some(value) = entry.get_mut(to).and_then(|item| item.as_value_mut())
+                {
+                    *value.decor_mut() = decor;
+                }
+                converted += 1;
+                Ok(())
+            })?;
+        }
+        Ok(converted)
+    }
+
+    /// Calls `f` on every `values` entry of `variable`, stopping at the first error.
+    fn for_each_entry<F>(&mut self, variable: &str, mut f: F) -> Result<(), String>
+    where
+        F: FnMut(&mut dyn TableLike) -> Result<(), String>,
+    {
+        match self.values_mut(variable)? {
+            Some(Item::ArrayOfTables(entries)) => {
+                for entry in entries.iter_mut() {
+                    f(entry)?;
+                }
+            }
+            Some(Item::Value(Value::Array(entries))) => {
+                for entry in entries.iter_mut().filter_map(|v| v.as_inline_table_mut()) {
+                    f(entry)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the `values` array of `variable`, or `None` if the variable doesn't exist.
+    fn values_mut(&mut self, variable: &str) -> Result<Option<&mut Item>, String> {
+        let Some(var_item) = self.doc.get_mut(variable) else {
+            return Ok(None);
+        };
+        let values = var_item
+            .as_table_like_mut()
+            .ok_or_else(|| format!("'{}' is not a table", variable))?
+            .get_mut("values");
+        match values {
+            Some(item @ (Item::ArrayOfTables(_) | Item::Value(Value::Array(_)))) => Ok(Some(item)),
+            None => Ok(None),
+            Some(_) => Err(format!("'{}.values' is not an array of tables", variable)),
+        }
+    }
+}
+
+/// Sets a string key on an entry, keeping the decoration (e.g. trailing comments) of the old value.
+fn set_string(entry: &mut dyn TableLike, key: &str, new: &str) {
+    let mut value = Value::from(new);
+    if let Some(old) = entry.get(key).and_then(|item| item.as_value()) {
+        *value.decor_mut() = old.decor().clone();
+    }
+    entry.insert(key, Item::Value(value));
+}
+
+/// Returns true if a `values` entry has exactly the given label and value.
+fn is_same_entry(entry: &dyn TableLike, label: &str, value: &str) -> bool {
+    entry.get("label").and_then(|l| l.as_str()) == Some(label)
+        && entry.get("value").and_then(|v| v.as_str()) == Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::Secret;
+
+    fn open_with(content: &str) -> (tempfile::TempDir, ConfigDocument) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        fs::write(&path, content).unwrap();
+        let doc = ConfigDocument::open(&path).unwrap();
+        (dir, doc)
+    }
+
+    #[test]
+    fn test_add_value_preserves_comments_and_order() {
+        let (_dir, mut doc) = open_with(
+            r#"# My keys
+[API_KEY]
+[[API_KEY.values]]
+label = "Dev" # the default
+value = "dev-key"
+"#,
+        );
+
+        assert!(doc.add_value("API_KEY", "Prod", "prod-key").unwrap());
+        assert!(doc.add_value("DB_HOST", "Local", "localhost").unwrap());
+
+        assert_eq!(
+            doc.doc.to_string(),
+            r#"# My keys
+[API_KEY]
+[[API_KEY.values]]
+label = "Dev" # the default
+value = "dev-key"
+
+[[API_KEY.values]]
+label = "Prod"
+value = "prod-key"
+
+[DB_HOST]
+[[DB_HOST.values]]
+label = "Local"
+value = "localhost"
+"#
+        );
+    }
+
+    #[test]
+    fn test_add_value_keeps_trailing_comments_above_new_table() {
+        let (_dir, mut doc) = open_with("# Starter template\n# [API_KEY]\n");
+
+        doc.add_value("API_KEY", "Dev", "dev-key").unwrap();
+        assert_eq!(
+            doc.doc.to_string(),
+            r#"# Starter template
+# [API_KEY]
+
+[API_KEY]
+[[API_KEY.values]]
+label = "Dev"
+value = "dev-key"
+"#
+        );
+
+        let (_dir, mut doc) = open_with("");
+        doc.add_value("API_KEY", "Dev", "dev-key").unwrap();
+        assert!(doc.doc.to_string().starts_with("[API_KEY]\n"));
+    }
+
+    #[test]
+    fn test_add_value_skips_exact_duplicates() {
+        let (_dir, mut doc) = open_with(
+            r#"[API_KEY]
+values = [{ label = "Dev", value = "dev-key" }]
+"#,
+        );
+
+        assert!(!doc.add_value("API_KEY", "Dev", "dev-key").unwrap());
+        assert!(doc.add_value("API_KEY", "Dev", "other-key").unwrap());
+        assert_eq!(doc.doc["API_KEY"]["values"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_add_value_rejects_invalid_names() {
+        let (_dir, mut doc) = open_with("");
+        let err = doc.add_value("X;evil", "Dev", "dev-key").unwrap_err();
+        assert!(err.contains("is not a valid variable name"), "{}", err);
+        assert!(!doc.doc.contains_key("X;evil"));
+    }
+
+    #[test]
+    fn test_save_round_trips_through_config_loader() {
+        let (dir, mut doc) = open_with("");
+        doc.add_value("API_KEY", "It's \"quoted\"", "a'b\"c")
+            .unwrap();
+        doc.save().unwrap();
+
+        let content = fs::read_to_string(dir.path().join(".env.swap.toml")).unwrap();
+        let config: crate::config::Config = toml::from_str(&content).unwrap();
+        assert_eq!(config["API_KEY"].values[0].label, "It's \"quoted\"");
+        assert_eq!(
+            config["API_KEY"].values[0]
+                .value
+                .as_ref()
+                .map(Secret::expose),
+            Some("a'b\"c")
+        );
+    }
+
+    #[test]
+    fn test_remove_value_and_variable() {
+        let (_dir, mut doc) = open_with(
+            r#"[API_KEY]
+# Development key
+[[API_KEY.values]]
+label = "Dev"
+value = "dev-key"
+
+[[API_KEY.values]]
+label = "Prod"
+value = "prod-key"
+
+[DB_HOST]
+values = [{ label = "Local", value = "localhost" }]
+"#,
+        );
+
+        assert_eq!(doc.remove_value("API_KEY", "Prod").unwrap(), 1);
+        assert_eq!(doc.remove_value("API_KEY", "Missing").unwrap(), 0);
+        assert_eq!(doc.remove_value("MISSING", "Dev").unwrap(), 0);
+        assert_eq!(
+            doc.doc.to_string(),
+            r#"[API_KEY]
+# Development key
+[[API_KEY.values]]
+label = "Dev"
+value = "dev-key"
+
+[DB_HOST]
+values = [{ label = "Local", value = "localhost" }]
+"#
+        );
+
+        // Removing the last value removes the variable entirely.
+        assert_eq!(doc.remove_value("DB_HOST", "Local").unwrap(), 1);
+        assert!(doc.doc.get("DB_HOST").is_none());
+
+        assert!(doc.remove_variable("API_KEY"));
+        assert!(!doc.remove_variable("API_KEY"));
+        assert_eq!(doc.doc.to_string(), "");
+    }
+
+    #[test]
+    fn test_rename_label_keeps_comments() {
+        let (_dir, mut doc) = open_with(
+            r#"[API_KEY]
+[[API_KEY.values]]
+label = "Dev" # the default
+value = "dev-key"
+"#,
+        );
+
+        assert_eq!(
+            doc.rename_label("API_KEY", "Dev", "Development").unwrap(),
+            1
+        );
+        assert_eq!(doc.rename_label("API_KEY", "Dev", "Other").unwrap(), 0);
+        assert_eq!(
+            doc.doc.to_string(),
+            r#"[API_KEY]
+[[API_KEY.values]]
+label = "Development" # the default
+value = "dev-key"
+"#
+        );
+    }
+
+    #[test]
+    fn test_convert_values() {
+        let (_dir, mut doc) = open_with(
+            r#"[settings]
+editor = "vim"
+
+[API_KEY]
+[[API_KEY.values]]
+label = "Dev"
+value = "dev-key" # plain
+
+[[API_KEY.values]]
+label = "Prod"
+value = "prod-key"
+
+[DB_HOST]
+values = [{ label = "Local", value = "localhost" }]
+"#,
+        );
+
+        let upper = |v: &str| Ok(v.to_uppercase());
+        assert_eq!(
+            doc.convert_values(Some("API_KEY"), Some("Dev"), "value", "value_enc", upper)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            doc.convert_values(None, None, "value", "value_enc", upper)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            doc.doc.to_string(),
+            r#"[settings]
+editor = "vim"
+
+[API_KEY]
+[[API_KEY.values]]
+label = "Dev"
+value_enc = "DEV-KEY" # plain
+
+[[API_KEY.values]]
+label = "Prod"
+value_enc = "PROD-KEY"
+
+[DB_HOST]
+values = [{ label = "Local", value_enc = "LOCALHOST" }]
+"#
+        );
+
+        let err = doc
+            .convert_values(
+                None,
+                None,
+                "value_enc",
+                "value",
+                |_| Err("boom".to_string()),
+            )
+            .unwrap_err();
+        assert_eq!(err, "boom");
+    }
+
+    #[test]
+    fn test_convert_values_writes_multiline_strings() {
+        let (_dir, mut doc) =
+            open_with("[API_KEY]\nvalues = [{ label = \"Dev\", value = \"k\" }]\n");
+        doc.convert_values(None, None, "value", "value_enc", |_| {
+            Ok("-----BEGIN-----\nabc\n-----END-----\n".to_string())
+        })
+        .unwrap();
+        let content = doc.doc.to_string();
+        let config: crate::config::Config = toml::from_str(&content).unwrap();
+        assert_eq!(
+            config["API_KEY"].values[0].value_enc.as_deref(),
+            Some("-----BEGIN-----\nabc\n-----END-----\n")
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_non_toml_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ConfigDocument::open(&dir.path().join(".env.swap.json")).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Encryption and decryption of `value_enc` values with age.
+//!
+//! Encrypted values are stored as ASCII-armored age files. They are decrypted
+//! either with the identity file named by the `age_identity` setting, or with
+//! a passphrase when they were encrypted to one.
+
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::{ExposeSecret, SecretString};
+use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient};
+
+use crate::config::Settings;
+use crate::i18n::I18nMessages;
+use crate::secret::Secret;
+
+/// The keys used to decrypt existing values and encrypt new ones.
+///
+/// Passphrases are asked for at most once per keyring, the first time they are needed.
+pub struct Keyring {
+    identity_file: Option<PathBuf>,
+    recipients: Vec<String>,
+    passphrase: RefCell<Option<SecretString>>,
+    new_passphrase: RefCell<Option<SecretString>>,
+    prompts: Prompts,
+}
+
+/// Localized prompts for reading passphrases.
+struct Prompts {
+    passphrase: String,
+    new_passphrase: String,
+    confirm_passphrase: String,
+    passphrase_mismatch: String,
+}
+
+impl Keyring {
+    /// Creates a keyring from the `age_identity` and `age_recipients` settings.
+    pub fn from_settings(settings: &Settings, messages: &I18nMessages) -> Self {
+        Keyring {
+            identity_file: settings
+                .age_identity
+                .as_deref()
+                .map(|file| crate::resolve::resolve_path(file, None)),
+            recipients: settings.age_recipients.clone().unwrap_or_default(),
+            passphrase: RefCell::new(None),
+            new_passphrase: RefCell::new(None),
+            prompts: Prompts {
+                passphrase: messages.get("passphrase_prompt").to_string(),
+                new_passphrase: messages.get("new_passphrase_prompt").to_string(),
+                confirm_passphrase: messages.get("confirm_passphrase_prompt").to_string(),
+                passphrase_mismatch: messages.get("passphrase_mismatch").to_string(),
+            },
+        }
+    }
+
+    /// Decrypts an armored age value.
+    pub fn decrypt(&self, armored: &str) -> Result<Secret, String> {
+        let decryptor = Decryptor::new_buffered(ArmoredReader::new(armored.trim().as_bytes()))
+            .map_err(|e| format!("Invalid encrypted value: {}", e))?;
+
+        let identities: Vec<Box<dyn Identity>> = if decryptor.is_scrypt() {
+            let passphrase = self.passphrase()?;
+            vec![Box::new(age::scrypt::Identity::new(passphrase))]
+        } else {
+            self.identity_file()?
+                .into_identities()
+                .map_err(|e| format!("Failed to load age identity: {}", e))?
+        };
+
+        let mut reader = decryptor
+            .decrypt(identities.iter().map(|i| i.as_ref()))
+            .map_err(|e| format!("Failed to decrypt value: {}", e))?;
+        // The plaintext is shorter than the armored ciphertext, so the buffer is never
+        // reallocated, which would leave an unwiped copy behind.
+        let mut plaintext = Secret::from(String::with_capacity(armored.len()));
+        reader
+            .read_to_string(plaintext.expose_mut())
+            .map_err(|e| format!("Failed to decrypt value: {}", e))?;
+        Ok(plaintext)
+    }
+
+    /// Encrypts a value to the configured recipients, or to a passphrase if there are none.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let recipients = self.encryption_recipients()?;
+        let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref()))
+            .map_err(|e| format!("Failed to encrypt value: {}", e))?;
+
+        let mut armored = Vec::new();
+        let result = ArmoredWriter::wrap_output(&mut armored, Format::AsciiArmor)
+            .and_then(|armor| encryptor.wrap_output(armor))
+            .and_then(|mut writer| {
+                writer.write_all(plaintext.as_bytes())?;
+                writer.finish()?.finish()
+            });
+        result.map_err(|e| format!("Failed to encrypt value: {}", e))?;
+
+        Ok(String::from_utf8(armored).expect("armored output is ASCII"))
+    }
+
+    /// Returns the recipients new values are encrypted to.
+    fn encryption_recipients(&self) -> Result<Vec<Box<dyn Recipient>>, String> {
+        let mut recipients: Vec<Box<dyn Recipient>> = Vec::new();
+        for recipient in &self.recipients {
+            let parsed: age::x25519::Recipient = recipient
+                .parse()
+                .map_err(|e| format!("Invalid age recipient {:?}: {}", recipient, e))?;
+            recipients.push(Box::new(parsed));
+        }
+        if recipients.is_empty() && self.identity_file.is_some() {
+            let from_identity = self
+                .identity_file()?
+                .to_recipients()
+                .map_err(|e| format!("Failed to load age identity: {}", e))?;
+            recipients.extend(
+                from_identity
+                    .into_iter()
+                    .map(|r| -> Box<dyn Recipient> { r }),
+            );
+        }
+        if recipients.is_empty() {
+            let passphrase = self.new_passphrase()?;
+            recipients.push(Box::new(age::scrypt::Recipient::new(passphrase)));
+        }
+        Ok(recipients)
+    }
+
+    fn identity_file(&self) -> Result<IdentityFile<age::NoCallbacks>, String> {
+        let path = self.identity_file.as_ref().ok_or_else(|| {
+            "No age identity is configured. Set `age_identity` in [settings].".to_string()
+        })?;
+        IdentityFile::from_file(path.to_string_lossy().into_owned())
+            .map_err(|e| format!("Failed to read age identity {:?}: {}", path, e))
+    }
+
+    /// Returns the passphrase for decrypting, asking for it the first time.
+    fn passphrase(&self) -> Result<SecretString, String> {
+        if let Some(passphrase) = self.passphrase.borrow().as_ref() {
+            return Ok(passphrase.clone());
+        }
+        let passphrase = read_passphrase(&self.prompts.passphrase)?;
+        *self.passphrase.borrow_mut() = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+
+    /// Returns the passphrase for encrypting, asking for it twice the first time.
+    fn new_passphrase(&self) -> Result<SecretString, String> {
+        if let Some(passphrase) = self.new_passphrase.borrow().as_ref() {
+            return Ok(passphrase.clone());
+        }
+        let passphrase = read_passphrase(&self.prompts.new_passphrase)?;
+        let confirmation = read_passphrase(&self.prompts.confirm_passphrase)?;
+        if passphrase.expose_secret() != confirmation.expose_secret() {
+            return Err(self.prompts.passphrase_mismatch.clone());
+        }
+        *self.new_passphrase.borrow_mut() = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+}
+
+/// Reads a passphrase from the terminal without echoing it.
+fn read_passphrase(prompt: &str) -> Result<SecretString, String> {
+    let passphrase = rpassword::prompt_password(prompt)
+        .map_err(|e| format!("Failed to read the passphrase: {}", e))?;
+    if passphrase.is_empty() {
+        return Err("The passphrase must not be empty.".to_string());
+    }
+    Ok(SecretString::from(passphrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn keyring(identity_file: Option<PathBuf>, recipients: Vec<String>) -> Keyring {
+        let messages = crate::i18n::load_messages().unwrap();
+        let settings = Settings {
+            age_identity: identity_file.map(|p| p.to_string_lossy().into_owned()),
+            age_recipients: Some(recipients),
+            ..Default::default()
+        };
+        Keyring::from_settings(&settings, &messages)
+    }
+
+    fn write_identity(dir: &std::path::Path) -> (PathBuf, age::x25519::Identity) {
+        let identity = age::x25519::Identity::generate();
+        let path = dir.join("identity.txt");
+        fs::write(&path, identity.to_string().expose_secret()).unwrap();
+        (path, identity)
+    }
+
+    #[test]
+    fn test_round_trip_with_identity_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, _) = write_identity(dir.path());
+        let keyring = keyring(Some(path), vec![]);
+
+        let armored = keyring.encrypt("prod-secret").unwrap();
+        assert!(armored.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert!(!armored.contains("prod-secret"));
+        assert_eq!(keyring.decrypt(&armored).unwrap().expose(), "prod-secret");
+    }
+
+    #[test]
+    fn test_encrypt_to_configured_recipients() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, _) = write_identity(dir.path());
+        let other = age::x25519::Identity::generate();
+
+        // Encrypted only to `other`, so our own identity can't read it back.
+        let encrypting = keyring(Some(path.clone()), vec![other.to_public().to_string()]);
+        let armored = encrypting.encrypt("secret").unwrap();
+        assert!(encrypting.decrypt(&armored).is_err());
+        assert_eq!(age::decrypt(&other, armored.as_bytes()).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_decrypt_with_passphrase() {
+        let passphrase = SecretString::from("correct horse".to_string());
+        let mut recipient = age::scrypt::Recipient::new(passphrase.clone());
+        recipient.set_work_factor(2);
+        let armored = age::encrypt_and_armor(&recipient, b"secret").unwrap();
+
+        let keyring = keyring(None, vec![]);
+        *keyring.passphrase.borrow_mut() = Some(passphrase);
+        assert_eq!(keyring.decrypt(&armored).unwrap().expose(), "secret");
+
+        *keyring.passphrase.borrow_mut() = Some(SecretString::from("wrong".to_string()));
+        assert!(keyring.decrypt(&armored).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_errors() {
+        let keyring = keyring(None, vec![]);
+        assert!(
+            keyring
+                .decrypt("not an age file")
+                .unwrap_err()
+                .starts_with("Invalid encrypted value")
+        );
+
+        let identity = age::x25519::Identity::generate();
+        let armored = age::encrypt_and_armor(&identity.to_public(), b"secret").unwrap();
+        assert!(
+            keyring
+                .decrypt(&armored)
+                .unwrap_err()
+                .contains("No age identity is configured")
+        );
+    }
+}
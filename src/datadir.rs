@@ -0,0 +1,108 @@
+//! Files gnb-envswap keeps in the user's data directory.
+//!
+//! Values are never written there in plaintext. Where a value has to be
+//! recognized later (the history log, `GNB_ENVSWAP_STATE`), it is hashed
+//! together with a random per-user salt, so the hash can't be brute-forced
+//! without reading the salt, which only its owner can. Values kept for `undo`
+//! are encrypted with a key that is only held by the shell session.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+const SALT_FILE: &str = "salt";
+
+/// Returns the directory gnb-envswap keeps its data in, which may not exist yet.
+pub fn path() -> Result<PathBuf, String> {
+    dirs::data_dir()
+        .map(|dir| dir.join("gnb-envswap"))
+        .ok_or_else(|| "Could not determine the user data directory.".to_string())
+}
+
+/// Returns the salt, creating it the first time it is needed.
+pub fn salt() -> Result<String, String> {
+    salt_in(&path()?)
+}
+
+/// Returns the salt kept in `dir`, creating it the first time it is needed.
+pub fn salt_in(dir: &Path) -> Result<String, String> {
+    let path = dir.join(SALT_FILE);
+    match fs::read_to_string(&path) {
+        Ok(salt) => return Ok(salt.trim().to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+
+    let salt = random_hex(32)?;
+    let mut file = open_private(&path, OpenOptions::new().write(true).create_new(true))?;
+    file.write_all(salt.as_bytes())
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    Ok(salt)
+}
+
+/// Opens a file in the data directory, creating it (and the directory) readable by its owner only.
+pub fn open_private(path: &Path, options: &mut OpenOptions) -> Result<File, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    options.create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))
+}
+
+/// Returns the hex-encoded SHA-256 hash of `value`, salted with `salt`.
+pub fn hash_value(salt: &str, value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(value.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+/// Returns `len` random bytes, hex-encoded.
+pub fn random_hex(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0u8; len];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate random bytes: {}", e))?;
+    Ok(to_hex(&bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_salt_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("data");
+        let salt = salt_in(&data).unwrap();
+        assert_eq!(salt.len(), 64);
+        assert_eq!(salt_in(&data).unwrap(), salt);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(data.join(SALT_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_hash_depends_on_salt() {
+        assert_eq!(hash_value("a", "secret"), hash_value("a", "secret"));
+        assert_ne!(hash_value("a", "secret"), hash_value("b", "secret"));
+        assert_eq!(hash_value("a", "secret").len(), 64);
+    }
+}
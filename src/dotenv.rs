@@ -0,0 +1,135 @@
+//! A minimal parser for `.env` (dotenv) files.
+//!
+//! Values are taken literally: unlike most dotenv loaders, `${VAR}` references
+//! are not expanded, so importing a file never bakes in the current environment.
+
+/// Parses the contents of a dotenv file into `(key, value)` pairs in file order.
+///
+/// Supported syntax:
+///
+/// * Blank lines and lines starting with `#` are ignored.
+/// * An optional `export ` prefix before the key.
+/// * Single-quoted values are taken verbatim.
+/// * Double-quoted values support `\n`, `\r`, `\t`, `\"` and `\\` escapes.
+/// * Unquoted values are trimmed and may end with a ` # comment`.
+pub fn parse(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut entries = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (key, raw_value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Line {}: expected KEY=VALUE", index + 1))?;
+        let key = key.trim();
+        if !crate::config::is_valid_variable_name(key) {
+            return Err(format!(
+                "Line {}: {}",
+                index + 1,
+                crate::config::invalid_variable_name(key)
+            ));
+        }
+
+        let value =
+            parse_value(raw_value.trim()).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        entries.push((key.to_string(), value));
+    }
+
+    Ok(entries)
+}
+
+/// Parses the right-hand side of a `KEY=VALUE` line.
+fn parse_value(raw: &str) -> Result<String, String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| "unterminated single-quoted value".to_string())?;
+        return Ok(rest[..end].to_string());
+    }
+
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        return Err("unterminated double-quoted value".to_string());
+    }
+
+    // Unquoted values end at an inline comment.
+    let value = match raw.find(" #") {
+        Some(pos) => &raw[..pos],
+        None => raw,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic_entries() {
+        let content = "
+            # Database settings
+            DB_HOST=localhost
+            export API_KEY = dev-key # inline comment
+
+            EMPTY=
+        ";
+        let entries = parse(content).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("DB_HOST".to_string(), "localhost".to_string()),
+                ("API_KEY".to_string(), "dev-key".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let content = r#"
+SINGLE='value # not a comment'
+DOUBLE="line1\nline2 \"quoted\""
+LITERAL='${NOT_EXPANDED}'
+"#;
+        let entries = parse(content).unwrap();
+        assert_eq!(entries[0].1, "value # not a comment");
+        assert_eq!(entries[1].1, "line1\nline2 \"quoted\"");
+        assert_eq!(entries[2].1, "${NOT_EXPANDED}");
+    }
+
+    #[test]
+    fn test_parse_errors_include_line_number() {
+        assert_eq!(
+            parse("OK=1\nNOT A PAIR").unwrap_err(),
+            "Line 2: expected KEY=VALUE"
+        );
+        assert_eq!(
+            parse("KEY=\"open").unwrap_err(),
+            "Line 1: unterminated double-quoted value"
+        );
+        // Keys end up unquoted in PowerShell commands, so only plain names are allowed.
+        for key in ["A;Start-Process(calc)#", "1ST", "MY KEY", "API-KEY"] {
+            let err = parse(&format!("{}=x", key)).unwrap_err();
+            assert!(err.contains("is not a valid variable name"), "{}", err);
+        }
+        assert!(parse("_PRIVATE_2=x").is_ok());
+    }
+}
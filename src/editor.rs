@@ -0,0 +1,107 @@
+//! Launches the user's text editor for the `edit` subcommand.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use crate::config::Settings;
+
+/// Finds the editor command from the `editor` setting, `$VISUAL` or `$EDITOR`, in that order.
+pub fn find_editor(settings: &Settings) -> Option<Vec<String>> {
+    resolve_editor(
+        settings.editor.as_deref(),
+        std::env::var("VISUAL").ok().as_deref(),
+        std::env::var("EDITOR").ok().as_deref(),
+    )
+}
+
+/// Picks the first non-empty editor command and splits it into program and arguments.
+fn resolve_editor(
+    setting: Option<&str>,
+    visual: Option<&str>,
+    editor: Option<&str>,
+) -> Option<Vec<String>> {
+    [setting, visual, editor]
+        .into_iter()
+        .flatten()
+        .map(split_command_line)
+        .find(|args| !args.is_empty())
+}
+
+/// Splits a command line on whitespace, keeping double-quoted parts together.
+///
+/// This is intentionally simple: the command is never passed to a shell.
+fn split_command_line(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Opens `path` in the editor and blocks until the editor exits.
+pub fn open_and_wait(command: &[String], path: &Path) -> io::Result<ExitStatus> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty editor command"))?;
+    Command::new(program).args(args).arg(path).status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line("vim"), vec!["vim"]);
+        assert_eq!(
+            split_command_line("  code   --wait "),
+            vec!["code", "--wait"]
+        );
+        assert_eq!(
+            split_command_line(r#""C:\Program Files\Editor\edit.exe" -n"#),
+            vec![r"C:\Program Files\Editor\edit.exe", "-n"]
+        );
+        assert_eq!(split_command_line(r#"ed """#), vec!["ed", ""]);
+        assert!(split_command_line("   ").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_editor_precedence() {
+        assert_eq!(
+            resolve_editor(Some("code --wait"), Some("vim"), Some("nano")),
+            Some(vec!["code".to_string(), "--wait".to_string()])
+        );
+        assert_eq!(
+            resolve_editor(None, Some("vim"), Some("nano")),
+            Some(vec!["vim".to_string()])
+        );
+        assert_eq!(
+            resolve_editor(Some(""), Some(" "), Some("nano")),
+            Some(vec!["nano".to_string()])
+        );
+        assert_eq!(resolve_editor(None, None, None), None);
+    }
+}
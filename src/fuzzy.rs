@@ -0,0 +1,198 @@
+//! fzf-style fuzzy matching for the TUI's search box.
+//!
+//! A query matches a candidate when its characters appear in the candidate in
+//! order, ignoring case. Among all the ways to match, the one with the best
+//! score is chosen: matches at the start of a word and runs of consecutive
+//! characters score higher, and gaps between matched characters cost a little.
+
+/// Points for every matched character.
+const SCORE_MATCH: i32 = 16;
+/// Bonus for a match at the start of a word (after `_`, `-`, a space, etc., or a camelCase hump).
+const BONUS_BOUNDARY: i32 = 8;
+/// Bonus for a match right after the previous matched character.
+const BONUS_CONSECUTIVE: i32 = 6;
+/// The first query character's bonus is multiplied by this, as in fzf.
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+/// Penalty for starting a gap between matched characters.
+const PENALTY_GAP_START: i32 = 3;
+/// Penalty for every further skipped character in a gap.
+const PENALTY_GAP_EXTENSION: i32 = 1;
+
+/// How well a query matched a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    /// The indices of the matched characters (not bytes) in the candidate, in order.
+    pub positions: Vec<usize>,
+}
+
+/// Matches `query` against `candidate`. An empty query matches everything with a score of 0.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<Match> {
+    let query: Vec<char> = query.chars().map(fold_case).collect();
+    let original: Vec<char> = candidate.chars().collect();
+    let text: Vec<char> = original.iter().copied().map(fold_case).collect();
+    if query.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    if query.len() > text.len() {
+        return None;
+    }
+
+    let bonus: Vec<i32> = (0..text.len())
+        .map(|j| boundary_bonus(&original, j))
+        .collect();
+    // scores[i][j]: the best score for matching query[..=i] with query[i] at text[j].
+    // from[i][j]: where query[i - 1] was matched in that case.
+    let mut scores = vec![vec![None; text.len()]; query.len()];
+    let mut from = vec![vec![0; text.len()]; query.len()];
+
+    for (i, &q) in query.iter().enumerate() {
+        for j in i..text.len() {
+            if text[j] != q {
+                continue;
+            }
+            if i == 0 {
+                scores[0][j] = Some(SCORE_MATCH + bonus[j] * FIRST_CHAR_MULTIPLIER);
+                continue;
+            }
+            let best = (i - 1..j)
+                .filter_map(|k| {
+                    let previous = scores[i - 1][k]?;
+                    let gap = (j - k - 1) as i32;
+                    let transition = if gap == 0 {
+                        BONUS_CONSECUTIVE
+                    } else {
+                        -(PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap - 1))
+                    };
+                    Some((previous + transition, k))
+                })
+                .max_by_key(|&(score, k)| (score, k));
+            if let Some((score, k)) = best {
+                scores[i][j] = Some(score + SCORE_MATCH + bonus[j]);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let last = query.len() - 1;
+    let (score, mut j) = (0..text.len())
+        .filter_map(|j| scores[last][j].map(|score| (score, j)))
+        .max_by_key(|&(score, j)| (score, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; query.len()];
+    for i in (0..query.len()).rev() {
+        positions[i] = j;
+        if i > 0 {
+            j = from[i][j];
+        }
+    }
+    Some(Match { score, positions })
+}
+
+/// Returns up to three of `candidates` that `name` was probably meant to be, best first.
+///
+/// A candidate is suggested if `name` fuzzily matches it, or if it is only a
+/// couple of edits away (to catch swapped or mistyped letters).
+pub fn suggestions<'a, I>(name: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let name_lower = name.to_lowercase();
+    let mut ranked: Vec<(usize, i32, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(&name_lower, &candidate.to_lowercase());
+            let score = fuzzy_match(name, candidate).map(|m| m.score);
+            (score.is_some() || distance <= 2).then(|| (distance, score.unwrap_or(0), candidate))
+        })
+        .collect();
+    ranked.sort_by_key(|&(distance, score, _)| (distance, std::cmp::Reverse(score)));
+    ranked
+        .into_iter()
+        .take(3)
+        .map(|(_, _, candidate)| candidate)
+        .collect()
+}
+
+/// Returns the Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the bonus for matching the character at `index`.
+fn boundary_bonus(text: &[char], index: usize) -> i32 {
+    let current = text[index];
+    let Some(&previous) = index.checked_sub(1).and_then(|i| text.get(i)) else {
+        return BONUS_BOUNDARY;
+    };
+    let after_separator = !previous.is_alphanumeric() && current.is_alphanumeric();
+    let camel_hump = previous.is_lowercase() && current.is_uppercase();
+    let digits_start = !previous.is_numeric() && current.is_numeric();
+    if after_separator || camel_hump || digits_start {
+        BONUS_BOUNDARY
+    } else {
+        0
+    }
+}
+
+/// Lowercases a character for case-insensitive comparison.
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, candidate).map(|m| m.positions)
+    }
+
+    fn score(query: &str, candidate: &str) -> i32 {
+        fuzzy_match(query, candidate).unwrap().score
+    }
+
+    #[test]
+    fn test_subsequence_matching() {
+        assert_eq!(positions("apky", "API_KEY"), Some(vec![0, 1, 4, 6]));
+        assert_eq!(positions("", "API_KEY"), Some(vec![]));
+        assert_eq!(positions("kpa", "API_KEY"), None);
+        assert_eq!(positions("API_KEYS", "API_KEY"), None);
+        assert_eq!(positions("本番", "<Work> 本番 (Prod)"), Some(vec![7, 8]));
+    }
+
+    #[test]
+    fn test_prefers_word_boundaries_and_runs() {
+        // "key" is matched at the start of the word, not inside "monkey".
+        assert_eq!(positions("key", "monkey_KEY"), Some(vec![7, 8, 9]));
+        // camelCase humps count as word starts.
+        assert_eq!(positions("dh", "dbHost"), Some(vec![0, 2]));
+
+        assert!(score("db", "DB_HOST") > score("db", "DEBUG"));
+        assert!(score("host", "DB_HOST") > score("host", "GHOST_LIST"));
+        assert!(score("prod", "Production") > score("prod", "Pre-release old"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let names = ["API_KEY", "API_URL", "DB_HOST", "DB_PORT"];
+        assert_eq!(suggestions("APIKEY", names), ["API_KEY"]);
+        assert_eq!(suggestions("AIP_KEY", names), ["API_KEY"]);
+        assert_eq!(suggestions("db_hots", names), ["DB_HOST"]);
+        assert_eq!(suggestions("DB_", names), ["DB_HOST", "DB_PORT"]);
+        assert!(suggestions("SECRET", names).is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
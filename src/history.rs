@@ -0,0 +1,156 @@
+//! The opt-in log of every variable gnb-envswap has set.
+//!
+//! When `history = true` is set in `[settings]`, each change is appended as a
+//! JSON line to `history.jsonl` in the user's data directory. Values are never
+//! written; each one is recorded as a SHA-256 hash salted with the per-user
+//! salt kept next to the log, so entries can be compared with each other
+//! without revealing the values.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::datadir::{self, open_private};
+
+const LOG_FILE: &str = "history.jsonl";
+
+/// A single recorded change of a variable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the command was printed, in RFC 3339 format (UTC).
+    pub time: String,
+    /// The directory gnb-envswap was run in.
+    pub cwd: String,
+    pub variable: String,
+    /// The label of the value. Undone changes have no label if the restored value had none.
+    pub label: Option<String>,
+    /// The config file the value came from.
+    pub source: Option<String>,
+    /// The salted hash of the value, or `None` if the variable was removed.
+    pub hash: Option<String>,
+    /// Whether the change was made by `undo`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undo: bool,
+}
+
+/// The history log and its salt.
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    /// Opens the history in the user's data directory.
+    pub fn open() -> Result<Self, String> {
+        Ok(Self::open_in(datadir::path()?))
+    }
+
+    /// Opens the history kept in `dir`, which may not exist yet.
+    fn open_in(dir: PathBuf) -> Self {
+        History { dir }
+    }
+
+    /// Builds an entry for a change made now, hashing `value` with the salt.
+    pub fn entry(
+        &self,
+        variable: &str,
+        label: Option<&str>,
+        source: Option<&Path>,
+        value: Option<&str>,
+    ) -> Result<HistoryEntry, String> {
+        let cwd = std::env::current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        let hash = match value {
+            Some(value) => Some(datadir::hash_value(&datadir::salt_in(&self.dir)?, value)),
+            None => None,
+        };
+        Ok(HistoryEntry {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            cwd,
+            variable: variable.to_string(),
+            label: label.map(str::to_string),
+            source: source.map(|p| p.display().to_string()),
+            hash,
+            undo: false,
+        })
+    }
+
+    /// Appends entries to the log.
+    pub fn append(&self, entries: &[HistoryEntry]) -> Result<(), String> {
+        let path = self.dir.join(LOG_FILE);
+        let mut lines = String::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| format!("Failed to write the history: {}", e))?;
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+        let mut file = open_private(&path, OpenOptions::new().append(true))?;
+        file.write_all(lines.as_bytes())
+            .map_err(|e| format!("Failed to write the history at {:?}: {}", path, e))
+    }
+
+    /// Reads every entry in the log, oldest first.
+    pub fn read(&self) -> Result<Vec<HistoryEntry>, String> {
+        let path = self.dir.join(LOG_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read the history at {:?}: {}", path, e)),
+        };
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    format!(
+                        "Failed to parse line {} of the history at {:?}: {}",
+                        i + 1,
+                        path,
+                        e
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::open_in(dir.path().join("data"));
+        assert!(history.read().unwrap().is_empty());
+
+        let first = history
+            .entry(
+                "API_KEY",
+                Some("Prod"),
+                Some(Path::new("/w/.env.swap.toml")),
+                Some("secret"),
+            )
+            .unwrap();
+        history.append(std::slice::from_ref(&first)).unwrap();
+        let mut second = history.entry("API_KEY", None, None, None).unwrap();
+        second.undo = true;
+        history.append(std::slice::from_ref(&second)).unwrap();
+
+        assert_eq!(history.read().unwrap(), [first.clone(), second]);
+        let log = fs::read_to_string(dir.path().join("data").join(LOG_FILE)).unwrap();
+        assert!(!log.contains("secret"));
+        assert_eq!(log.lines().count(), 2);
+
+        // The same value always gets the same hash, as the salt is kept.
+        let again = history
+            .entry("API_KEY", None, None, Some("secret"))
+            .unwrap();
+        assert_eq!(again.hash, first.hash);
+    }
+}
@@ -75,7 +75,26 @@ mod tests {
         const MESSAGES_JSON: &str = include_str!("../i18n/messages.json");
         let config: I18nConfig = serde_json::from_str(MESSAGES_JSON).unwrap();
 
-        assert_eq!(config.en.get("select_variable"), Some(&"Select an environment variable".to_string()));
-        assert_eq!(config.ja.get("select_variable"), Some(&"環境変数を選択してください".to_string()));
+        assert_eq!(
+            config.en.get("select_variable"),
+            Some(&"Select an environment variable".to_string())
+        );
+        assert_eq!(
+            config.ja.get("select_variable"),
+            Some(&"環境変数を選択してください".to_string())
+        );
+    }
+
+    #[test]
+    fn test_config_template_is_valid_and_empty() {
+        // The template written by `edit` must parse, and define nothing until uncommented.
+        const MESSAGES_JSON: &str = include_str!("../i18n/messages.json");
+        let config: I18nConfig = serde_json::from_str(MESSAGES_JSON).unwrap();
+
+        for messages in [&config.en, &config.ja] {
+            let template = messages.get("config_template").unwrap();
+            let parsed: toml::Table = toml::from_str(template).unwrap();
+            assert!(parsed.is_empty());
+        }
     }
 }
@@ -0,0 +1,255 @@
+//! Expands `${VAR}` references in inline values.
+//!
+//! A reference is resolved against the other variables set in the same run
+//! first, then against the current environment. `$$` is a literal `$`, and a
+//! `$` that does not start a reference is kept as is.
+
+use std::collections::HashMap;
+
+use crate::secret::Secret;
+
+/// A variable and the value it is about to be set to.
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub variable: String,
+    pub value: Secret,
+    /// Whether `value` may contain references. Only inline values are expanded.
+    pub expand: bool,
+}
+
+/// Expands the references in every assignment, reading the environment with `env`.
+///
+/// A variable that refers to itself gets the value it currently has in the
+/// environment, so `PATH = "${PATH};C:\\tools"` appends to the existing value.
+pub fn expand_assignments<F>(assignments: &mut [Assignment], env: F) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expander = Expander {
+        assignments: &*assignments,
+        env,
+        expanded: HashMap::new(),
+        stack: Vec::new(),
+        undefined: Vec::new(),
+    };
+    let values = (0..assignments.len())
+        .map(|i| expander.expand_variable(i))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !expander.undefined.is_empty() {
+        let list: Vec<String> = expander
+            .undefined
+            .iter()
+            .map(|(owner, name)| format!("${{{}}} in {}", name, owner))
+            .collect();
+        return Err(format!(
+            "Undefined variable reference(s): {}",
+            list.join(", ")
+        ));
+    }
+
+    for (assignment, value) in assignments.iter_mut().zip(values) {
+        assignment.value = value;
+    }
+    Ok(())
+}
+
+struct Expander<'a, F> {
+    assignments: &'a [Assignment],
+    env: F,
+    /// Fully expanded values, by assignment index.
+    expanded: HashMap<usize, Secret>,
+    /// The variables currently being expanded, to detect cycles.
+    stack: Vec<usize>,
+    /// `(owner, name)` pairs of references that could not be resolved.
+    undefined: Vec<(String, String)>,
+}
+
+impl<F> Expander<'_, F>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn expand_variable(&mut self, index: usize) -> Result<Secret, String> {
+        if let Some(value) = self.expanded.get(&index) {
+            return Ok(value.clone());
+        }
+        let assignment = &self.assignments[index];
+        if !assignment.expand {
+            return Ok(assignment.value.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|&i| i == index) {
+            let mut cycle: Vec<&str> = self.stack[start..]
+                .iter()
+                .map(|&i| self.assignments[i].variable.as_str())
+                .collect();
+            cycle.push(&assignment.variable);
+            return Err(format!(
+                "Circular variable reference: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        self.stack.push(index);
+        let value = self.expand_str(index);
+        self.stack.pop();
+        let value = value?;
+        self.expanded.insert(index, value.clone());
+        Ok(value)
+    }
+
+    fn expand_str(&mut self, owner: usize) -> Result<Secret, String> {
+        let template = self.assignments[owner].value.expose();
+        let owner_name = &self.assignments[owner].variable;
+        let mut result = Secret::from(String::with_capacity(template.len()));
+        let mut rest = template;
+
+        while let Some(pos) = rest.find('$') {
+            result.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+            if let Some(after) = rest.strip_prefix("$$") {
+                result.push('$');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| format!("Unterminated \"${{\" in {}", owner_name))?;
+                let name = &after[..end];
+                if name.is_empty() {
+                    return Err(format!("Empty \"${{}}\" reference in {}", owner_name));
+                }
+                result.push_str(self.lookup(name, owner)?.expose());
+                rest = &after[end + 1..];
+            } else {
+                result.push('$');
+                rest = &rest[1..];
+            }
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    fn lookup(&mut self, name: &str, owner: usize) -> Result<Secret, String> {
+        let assignments = self.assignments;
+        if assignments[owner].variable != name
+            && let Some(index) = assignments.iter().position(|a| a.variable == name)
+        {
+            return self.expand_variable(index);
+        }
+        if let Some(value) = (self.env)(name) {
+            return Ok(Secret::from(value));
+        }
+        let reference = (assignments[owner].variable.clone(), name.to_string());
+        if !self.undefined.contains(&reference) {
+            self.undefined.push(reference);
+        }
+        Ok(Secret::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(variable: &str, value: &str) -> Assignment {
+        Assignment {
+            variable: variable.to_string(),
+            value: value.into(),
+            expand: true,
+        }
+    }
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "DB_USER" => Some("admin".to_string()),
+            "PATH" => Some("/usr/bin".to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand(assignments: &mut [Assignment]) -> Result<Vec<String>, String> {
+        expand_assignments(assignments, env)?;
+        Ok(assignments
+            .iter()
+            .map(|a| a.value.expose().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_expand_from_assignments_and_environment() {
+        let mut assignments = [
+            assignment("DATABASE_URL", "postgres://${DB_USER}@${DB_HOST}/app"),
+            assignment("DB_HOST", "${DB_NAME}.internal"),
+            assignment("DB_NAME", "db1"),
+        ];
+        assert_eq!(
+            expand(&mut assignments).unwrap(),
+            ["postgres://admin@db1.internal/app", "db1.internal", "db1"]
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_plain_dollars() {
+        let mut assignments = [assignment("PRICE", "$$5 costs $5 ${DB_USER}$")];
+        assert_eq!(expand(&mut assignments).unwrap(), ["$5 costs $5 admin$"]);
+    }
+
+    #[test]
+    fn test_self_reference_reads_environment() {
+        let mut assignments = [assignment("PATH", "${PATH}:/opt/bin")];
+        assert_eq!(expand(&mut assignments).unwrap(), ["/usr/bin:/opt/bin"]);
+    }
+
+    #[test]
+    fn test_unexpanded_values_are_kept_verbatim() {
+        let mut assignments = [
+            Assignment {
+                expand: false,
+                ..assignment("SECRET", "${DB_USER}$$")
+            },
+            assignment("URL", "${SECRET}"),
+        ];
+        assert_eq!(
+            expand(&mut assignments).unwrap(),
+            ["${DB_USER}$$", "${DB_USER}$$"]
+        );
+    }
+
+    #[test]
+    fn test_cycle_is_reported() {
+        let mut assignments = [
+            assignment("A", "${B}"),
+            assignment("B", "x${C}"),
+            assignment("C", "${A}"),
+        ];
+        assert_eq!(
+            expand(&mut assignments).unwrap_err(),
+            "Circular variable reference: A -> B -> C -> A"
+        );
+    }
+
+    #[test]
+    fn test_undefined_references_are_listed() {
+        let mut assignments = [
+            assignment("URL", "${HOST}:${PORT}/${HOST}"),
+            assignment("OTHER", "${MISSING}"),
+        ];
+        assert_eq!(
+            expand(&mut assignments).unwrap_err(),
+            "Undefined variable reference(s): ${HOST} in URL, ${PORT} in URL, ${MISSING} in OTHER"
+        );
+        // Nothing is changed when expansion fails.
+        assert_eq!(assignments[0].value.expose(), "${HOST}:${PORT}/${HOST}");
+    }
+
+    #[test]
+    fn test_malformed_references() {
+        let mut assignments = [assignment("URL", "${HOST")];
+        assert!(
+            expand(&mut assignments)
+                .unwrap_err()
+                .starts_with("Unterminated")
+        );
+        let mut assignments = [assignment("URL", "${}")];
+        assert!(expand(&mut assignments).unwrap_err().starts_with("Empty"));
+    }
+}
@@ -1,16 +1,44 @@
 mod app;
 mod cli;
 mod config;
+mod config_edit;
+mod crypto;
+mod datadir;
+mod dotenv;
+mod editor;
+mod fuzzy;
+mod history;
 mod i18n;
+mod interpolate;
 mod output;
+mod permissions;
+mod resolve;
+mod secret;
+mod state;
+mod trust;
 mod tui;
+mod undo;
 
-use std::{env, fs};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
 
 use app::App;
 use clap::Parser;
-use cli::{Cli, Commands, EditTarget, ShowArgs};
+use cli::{
+    AddArgs, AllowArgs, Cli, Commands, CryptArgs, EditArgs, EditTarget, HistoryArgs, ImportArgs,
+    RekeyArgs, RemoveArgs, RenameLabelArgs, SetArgs, ShowArgs, UndoArgs,
+};
+use config_edit::ConfigDocument;
+use crypto::Keyring;
+use history::History;
 use i18n::I18nMessages;
+use interpolate::Assignment;
+use permissions::PermissionProblem;
+use secret::Secret;
+use state::SessionState;
+use trust::{AllowList, TrustStatus};
+use undo::{KeptValues, UndoStack};
 
 fn main() -> std::io::Result<()> {
     let cli = Cli::parse();
@@ -28,83 +56,706 @@ fn main() -> std::io::Result<()> {
     if let Some(command) = cli.command {
         match command {
             Commands::Edit(args) => {
-                handle_edit_command(args.target, &messages);
+                handle_edit_command(args, &messages);
                 return Ok(());
             }
             Commands::Show(args) => {
                 handle_show_command(args, &messages);
                 return Ok(());
             }
+            Commands::Set(args) => {
+                handle_set_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Undo(args) => {
+                handle_undo_command(args, &messages);
+                return Ok(());
+            }
+            Commands::History(args) => {
+                handle_history_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Import(args) => {
+                handle_import_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Add(args) => {
+                handle_add_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Remove(args) => {
+                handle_remove_command(args, &messages);
+                return Ok(());
+            }
+            Commands::RenameLabel(args) => {
+                handle_rename_label_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Encrypt(args) => {
+                handle_encrypt_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Decrypt(args) => {
+                handle_decrypt_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Rekey(args) => {
+                handle_rekey_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Allow(args) => {
+                handle_allow_command(args, &messages);
+                return Ok(());
+            }
+            Commands::Deny(args) => {
+                handle_deny_command(args, &messages);
+                return Ok(());
+            }
+            Commands::FixPermissions => {
+                handle_fix_permissions_command(&messages);
+                return Ok(());
+            }
+            Commands::Init => {
+                println!("{}", output::generate_powershell_init());
+                return Ok(());
+            }
         }
     }
 
     // --- Default action: Run the TUI ---
-    run_tui_mode(&messages)?;
+    run_tui_mode(&messages, cli.force_stdout, cli.variable.as_deref())?;
 
     Ok(())
 }
 
-/// The main logic for running the TUI application.
-fn run_tui_mode(messages: &I18nMessages) -> std::io::Result<()> {
-    let config = match config::load_config() {
-        Ok(config) => config,
-        Err(err) => {
-            if err.contains("No .env.swap.toml file found") {
-                eprintln!("{}", messages.get("config_not_found"));
+/// Loads the merged configuration, exiting with a localized message if there is none.
+fn load_config_or_exit(messages: &I18nMessages) -> config::LoadedConfig {
+    let loaded =
+        match config::load_config(|path, content| is_local_config_allowed(path, content, messages))
+        {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                if err.contains("No .env.swap.toml file found") {
+                    eprintln!("{}", messages.get("config_not_found"));
+                    std::process::exit(1);
+                }
+                eprintln!("Error loading config: {}", err);
                 std::process::exit(1);
             }
-            eprintln!("Error loading config: {}", err);
-            std::process::exit(1);
+        };
+
+    if loaded.variables.is_empty() {
+        eprintln!("{}", messages.get("config_not_found"));
+        std::process::exit(1);
+    }
+    for (path, problem) in &loaded.insecure_files {
+        eprintln!("{}", describe_permission_problem(path, problem, messages));
+    }
+    if !loaded.insecure_files.is_empty() {
+        eprintln!("{}", messages.get("fix_permissions_hint"));
+    }
+    loaded
+}
+
+/// Resolves the selected values and prints the PowerShell commands that set them and their
+/// `also` variables.
+///
+/// `selections` are (variable, value) pairs. A variable selected directly wins over the same
+/// variable in another value's `also` table; between `also` tables, the first one wins.
+fn emit_assignments(
+    selections: &[(&str, &config::EnvValue)],
+    loaded: &config::LoadedConfig,
+    messages: &I18nMessages,
+) -> Result<(), String> {
+    // Values kept outside the config file are only read now that they were chosen.
+    let keyring = Keyring::from_settings(&loaded.settings, messages);
+    let mut assignments = Vec::new();
+    // The selection each assignment comes from, at the same index.
+    let mut origins = Vec::new();
+    for &(variable_name, env_value) in selections {
+        assignments.push(Assignment {
+            variable: variable_name.to_string(),
+            value: resolve::resolve_value(env_value, &keyring)?,
+            expand: env_value.interpolate && env_value.value.is_some(),
+        });
+        origins.push((variable_name, env_value));
+    }
+    for &(variable_name, env_value) in selections {
+        for (variable, value) in &env_value.also {
+            if assignments.iter().any(|a| a.variable == *variable) {
+                continue;
+            }
+            assignments.push(Assignment {
+                variable: variable.clone(),
+                value: value.clone(),
+                expand: env_value.interpolate,
+            });
+            origins.push((variable_name, env_value));
         }
+    }
+    interpolate::expand_assignments(&mut assignments, |name| env::var(name).ok())?;
+
+    let mut state = SessionState::from_env()?;
+
+    // Remember what the variables held before, so `undo` can restore them.
+    let mut undo_stack = UndoStack::from_env();
+    let changed = assignments.iter().map(|a| a.variable.as_str());
+    let (mut undo_entry, kept_values) = undo::capture(
+        changed,
+        |name| env::var(name).ok(),
+        &state,
+        &loaded.variables,
+    );
+    let kept = KeptValues::open()?;
+    if !kept_values.is_empty() {
+        let (name, key) = kept.keep(&kept_values)?;
+        undo_entry.kept = Some(name);
+        undo_entry.key = Some(key);
+    }
+    if let Some(name) = undo_stack.push(undo_entry).and_then(|dropped| dropped.kept) {
+        kept.forget(&name)?;
+    }
+
+    // Remember what was set, so `show` and the TUI can tell which label is active.
+    for (assignment, &(variable_name, env_value)) in assignments.iter().zip(&origins) {
+        // A value typed in the TUI and not saved has no label; it shows up as a custom value.
+        if env_value.label.is_empty() {
+            continue;
+        }
+        let via = (assignment.variable != variable_name).then_some(variable_name);
+        state.record(
+            &assignment.variable,
+            &env_value.label,
+            env_value.source.as_deref(),
+            via,
+            assignment.value.expose(),
+        );
+    }
+
+    if let Some(history) = open_history(&loaded.settings)? {
+        let entries = assignments
+            .iter()
+            .zip(&origins)
+            .map(|(assignment, (_, env_value))| {
+                history.entry(
+                    &assignment.variable,
+                    Some(env_value.label.as_str()).filter(|label| !label.is_empty()),
+                    env_value.source.as_deref(),
+                    Some(assignment.value.expose()),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        history.append(&entries)?;
+    }
+
+    for assignment in &assignments {
+        let command = Secret::from(output::generate_powershell_command(
+            &assignment.variable,
+            assignment.value.expose(),
+        ));
+        println!("{}", command.expose());
+    }
+    let command = output::generate_powershell_command(state::STATE_VARIABLE, &state.to_json());
+    println!("{}", command);
+    print_undo_stack(&undo_stack);
+    Ok(())
+}
+
+/// Prints the commands that restore the variables changed by the latest change.
+///
+/// Configured values are resolved from `loaded` again, as the undo stack only names them.
+/// Restoring a value marked with `confirm` fails unless `confirmed`.
+fn emit_undo(
+    loaded: &config::LoadedConfig,
+    confirmed: bool,
+    messages: &I18nMessages,
+) -> Result<(), String> {
+    let mut undo_stack = UndoStack::from_env();
+    let Some(entry) = undo_stack.pop() else {
+        return Err(messages.get("nothing_to_undo").to_string());
     };
+    if !confirmed && let Some((variable, env_value)) = undo_confirmations(&entry, loaded).first() {
+        return Err(messages
+            .get("confirm_required")
+            .replace("{variable}", variable)
+            .replace("{label}", &env_value.label));
+    }
 
-    if config.is_empty() {
-        eprintln!("{}", messages.get("config_not_found"));
+    let kept = KeptValues::open()?;
+    let mut kept_values = match (&entry.kept, &entry.key) {
+        (Some(name), Some(key)) => kept.read(name, key)?.unwrap_or_default(),
+        _ => Default::default(),
+    };
+    let keyring = Keyring::from_settings(&loaded.settings, messages);
+    let mut assignments = Vec::new();
+    // The configured value each assignment restores, at the same index.
+    let mut origins = Vec::new();
+    let mut unset = Vec::new();
+    for (variable, previous) in &entry.previous {
+        match previous {
+            undo::Previous::NotSet => unset.push(variable.as_str()),
+            undo::Previous::Configured(configured) => {
+                let (env_value, value, expand) =
+                    find_previous_value(variable, configured, loaded, &keyring, messages)?;
+                assignments.push(Assignment {
+                    variable: variable.clone(),
+                    value,
+                    expand,
+                });
+                origins.push(Some(env_value));
+            }
+            undo::Previous::Kept => {
+                let Some(value) = kept_values.remove(variable) else {
+                    return Err(messages
+                        .get("undo_value_lost")
+                        .replace("{variable}", variable));
+                };
+                assignments.push(Assignment {
+                    variable: variable.clone(),
+                    value,
+                    expand: false,
+                });
+                origins.push(None);
+            }
+        }
+    }
+    interpolate::expand_assignments(&mut assignments, |name| env::var(name).ok())?;
+
+    if let Some(history) = open_history(&loaded.settings)? {
+        let mut entries = Vec::new();
+        for (assignment, origin) in assignments.iter().zip(&origins) {
+            let mut history_entry = history.entry(
+                &assignment.variable,
+                origin.map(|v| v.label.as_str()),
+                origin.and_then(|v| v.source.as_deref()),
+                Some(assignment.value.expose()),
+            )?;
+            history_entry.undo = true;
+            entries.push(history_entry);
+        }
+        for variable in &unset {
+            let mut history_entry = history.entry(variable, None, None, None)?;
+            history_entry.undo = true;
+            entries.push(history_entry);
+        }
+        history.append(&entries)?;
+    }
+
+    for assignment in &assignments {
+        let command = Secret::from(output::generate_powershell_command(
+            &assignment.variable,
+            assignment.value.expose(),
+        ));
+        println!("{}", command.expose());
+    }
+    for variable in &unset {
+        println!("{}", output::generate_powershell_unset_command(variable));
+    }
+    let command = match &entry.state {
+        Some(state) => output::generate_powershell_command(state::STATE_VARIABLE, state),
+        None => output::generate_powershell_unset_command(state::STATE_VARIABLE),
+    };
+    println!("{}", command);
+    print_undo_stack(&undo_stack);
+    if let Some(name) = &entry.kept {
+        kept.forget(name)?;
+    }
+
+    let restored: Vec<&str> = entry.previous.keys().map(String::as_str).collect();
+    eprintln!(
+        "{}",
+        messages
+            .get("undo_restored")
+            .replace("{variables}", &restored.join(", "))
+    );
+    Ok(())
+}
+
+/// Finds the configured value a variable held before a change and resolves it again.
+///
+/// Returns the value entry it belongs to, the value and whether it may be expanded.
+fn find_previous_value<'a>(
+    variable: &str,
+    configured: &undo::ConfiguredValue,
+    loaded: &'a config::LoadedConfig,
+    keyring: &Keyring,
+    messages: &I18nMessages,
+) -> Result<(&'a config::EnvValue, Secret, bool), String> {
+    let env_value = find_configured_value(variable, configured, loaded);
+    let missing = || {
+        messages
+            .get("undo_value_missing")
+            .replace("{variable}", variable)
+            .replace("{label}", &configured.label)
+    };
+    let env_value = env_value.ok_or_else(missing)?;
+    if configured.via.is_none() {
+        let value = resolve::resolve_value(env_value, keyring)?;
+        let expand = env_value.interpolate && env_value.value.is_some();
+        return Ok((env_value, value, expand));
+    }
+    let value = env_value.also.get(variable).ok_or_else(missing)?;
+    Ok((env_value, value.clone(), env_value.interpolate))
+}
+
+/// Finds the value `configured` names, which is the one selected through `via` for an
+/// `also` variable.
+fn find_configured_value<'a>(
+    variable: &str,
+    configured: &undo::ConfiguredValue,
+    loaded: &'a config::LoadedConfig,
+) -> Option<&'a config::EnvValue> {
+    let selected = configured.via.as_deref().unwrap_or(variable);
+    let same_source = |env_value: &config::EnvValue| match &configured.source {
+        Some(source) => env_value
+            .source
+            .as_ref()
+            .is_some_and(|path| path.display().to_string() == *source),
+        None => true,
+    };
+    loaded.variables.get(selected).and_then(|var| {
+        var.values
+            .iter()
+            .find(|v| v.label == configured.label && same_source(v))
+    })
+}
+
+/// Returns the values `entry` restores that are marked with `confirm`, with their variables.
+fn undo_confirmations<'a>(
+    entry: &'a undo::UndoEntry,
+    loaded: &'a config::LoadedConfig,
+) -> Vec<(&'a str, &'a config::EnvValue)> {
+    let mut confirmations = Vec::new();
+    for (variable, previous) in &entry.previous {
+        if let undo::Previous::Configured(configured) = previous
+            && let Some(env_value) = find_configured_value(variable, configured, loaded)
+            && env_value.confirm
+        {
+            confirmations.push((variable.as_str(), env_value));
+        }
+    }
+    confirmations
+}
+
+/// Prints the command that stores the undo stack, or removes it once it is empty.
+fn print_undo_stack(undo_stack: &UndoStack) {
+    let command = if undo_stack.is_empty() {
+        output::generate_powershell_unset_command(undo::UNDO_VARIABLE)
+    } else {
+        output::generate_powershell_command(undo::UNDO_VARIABLE, &undo_stack.to_json())
+    };
+    println!("{}", command);
+}
+
+/// Exits with a hint about the `Invoke-Expression` wrapper if stdout is a terminal.
+///
+/// Printing the command there would show the secret on screen and leave it in the
+/// scrollback, without setting anything.
+fn ensure_stdout_is_captured(force_stdout: bool, messages: &I18nMessages) {
+    if !output::can_print_commands(force_stdout, io::stdout().is_terminal()) {
+        eprintln!("{}", messages.get("stdout_is_terminal"));
         std::process::exit(1);
     }
+}
+
+/// The main logic for running the TUI application.
+///
+/// With `variable`, the TUI opens on the value list of that variable.
+fn run_tui_mode(
+    messages: &I18nMessages,
+    force_stdout: bool,
+    variable: Option<&str>,
+) -> std::io::Result<()> {
+    ensure_stdout_is_captured(force_stdout, messages);
+    let loaded = load_config_or_exit(messages);
+    let variable = variable.map(|name| find_variable_name(&loaded.variables, name, messages));
+    let variable = match variable.transpose() {
+        Ok(variable) => variable,
+        Err(err) => {
+            drop(loaded);
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let mut app = App::new(&config, messages);
+    let mut app = App::new(&loaded.variables, messages);
+    // Without the salt the state can't be checked; statuses fall back to the config.
+    app.session = SessionState::from_env().unwrap_or_default();
+    app.search_values = loaded.settings.search_values;
+    app.auto_select_single = loaded.settings.auto_select_single;
+    app.allow_reveal = loaded.settings.allows_reveal();
+    let undo_stack = UndoStack::from_env();
+    app.can_undo = !undo_stack.is_empty();
+    if let Some(entry) = undo_stack.latest() {
+        app.undo_confirmations = undo_confirmations(entry, &loaded)
+            .into_iter()
+            .map(|(variable, value)| (variable.to_string(), value.label.clone()))
+            .collect();
+    }
+    if let Some(variable) = variable {
+        app.enter_variable(&variable);
+    }
     tui::run_tui(&mut app)?;
 
-    if let (Some(variable_name), Some(value_index)) =
-        (app.selected_variable, app.value_list_state.selected())
+    let result = match (&app.selected_variable, app.selected_value()) {
+        // Values that need confirmation were confirmed in the TUI.
+        _ if app.undo_requested => emit_undo(&loaded, true, messages),
+        (Some(variable), _) if app.custom_value().is_some() => {
+            emit_custom_value(&app, variable, &loaded, messages)
+        }
+        (Some(_), Some(_)) => emit_assignments(&app.selections(), &loaded, messages),
+        // Applied with Ctrl+S from the variable list; quitting with Esc drops the chosen values.
+        (None, _) if !app.pending.is_empty() => {
+            emit_assignments(&app.selections(), &loaded, messages)
+        }
+        _ => Ok(()),
+    };
+
+    // Wipe the loaded values now, since exiting the process would skip their destructors.
+    drop(app);
+    drop(loaded);
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Sets the value typed in the TUI for `variable`, along with the values chosen with Tab.
+///
+/// If the user chose to save it, it is added to the local or global config file first.
+fn emit_custom_value(
+    app: &App,
+    variable: &str,
+    loaded: &config::LoadedConfig,
+    messages: &I18nMessages,
+) -> Result<(), String> {
+    let Some(input) = app.custom_value() else {
+        return Ok(());
+    };
+    let value = input.value.clone();
+    let mut custom = config::EnvValue {
+        value: Some(value.clone()),
+        ..Default::default()
+    };
+    if let Some(global) = input.save_global {
+        let label = input.label.trim();
+        let mut doc = open_target_document(global);
+        if doc.add_value(variable, label, value.expose())? {
+            save_document(&doc);
+            eprintln!("{}", messages.get("value_added"));
+        } else {
+            eprintln!("{}", messages.get("value_already_exists"));
+        }
+        let prefix = if global {
+            config::HOME_PREFIX
+        } else {
+            config::WORK_PREFIX
+        };
+        custom.label = format!("{}{}", prefix, label);
+        custom.source = Some(doc.path().to_path_buf());
+    }
+
+    let mut selections = app.selections();
+    selections.push((variable, &custom));
+    emit_assignments(&selections, loaded, messages)
+}
+
+/// Finds the configured variable `name` refers to, ignoring case as Windows does.
+///
+/// If there is none, the error suggests similarly named variables.
+fn find_variable_name(
+    config: &config::Config,
+    name: &str,
+    messages: &I18nMessages,
+) -> Result<String, String> {
+    if config.contains_key(name) {
+        return Ok(name.to_string());
+    }
+    let mut names: Vec<&str> = config.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    if let Some(found) = names.iter().find(|n| n.eq_ignore_ascii_case(name)) {
+        return Ok(found.to_string());
+    }
+
+    let mut message = messages
+        .get("variable_not_defined")
+        .replace("{variable}", name);
+    let suggestions = fuzzy::suggestions(name, names);
+    if !suggestions.is_empty() {
+        message.push('\n');
+        message.push_str(
+            &messages
+                .get("did_you_mean")
+                .replace("{variables}", &suggestions.join(", ")),
+        );
+    }
+    Err(message)
+}
+
+/// Returns a localized description of a permission problem with the config file at `path`.
+fn describe_permission_problem(
+    path: &Path,
+    problem: &PermissionProblem,
+    messages: &I18nMessages,
+) -> String {
+    match problem {
+        PermissionProblem::TooOpen(mode) => messages
+            .get("permissions_too_open")
+            .replace("{mode}", &format!("{:o}", mode)),
+        PermissionProblem::OtherOwner(uid) => messages
+            .get("permissions_other_owner")
+            .replace("{uid}", &uid.to_string()),
+    }
+    .replace("{path}", &path.display().to_string())
+}
+
+/// Decides whether the local config at `path`, read as `content`, may be used, asking the user
+/// if it is new or changed.
+fn is_local_config_allowed(path: &Path, content: &[u8], messages: &I18nMessages) -> bool {
+    let result = AllowList::load()
+        .and_then(|list| list.status_of(path, content).map(|status| (list, status)));
+    let (mut allow_list, status) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let message = match status {
+        TrustStatus::Allowed => return true,
+        TrustStatus::Changed => messages.get("config_changed"),
+        TrustStatus::Unknown => messages.get("config_not_allowed"),
+    };
+    eprintln!("{}", message.replace("{path}", &path.display().to_string()));
+
+    if io::stdin().is_terminal() && ask_yes_no(messages.get("allow_prompt"), false) {
+        if let Err(err) = allow_list
+            .allow_content(path, content)
+            .and_then(|()| allow_list.save())
+        {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return true;
+    }
+    eprintln!("{}", messages.get("config_ignored"));
+    false
+}
+
+/// Runs `write`, which changes the config file at `path`, keeping the file allowed if it was.
+///
+/// Files created by `write` are allowed as well, since their contents come from the user.
+fn keep_allowed<T>(path: &Path, write: impl FnOnce() -> T) -> T {
+    let allow_list = AllowList::load();
+    let was_allowed = !path.exists()
+        || allow_list
+            .as_ref()
+            .is_ok_and(|list| list.status(path) == Ok(TrustStatus::Allowed));
+
+    let result = write();
+
+    if was_allowed
+        && path.exists()
+        && let Ok(mut list) = allow_list
+        && let Err(err) = list.allow(path).and_then(|()| list.save())
     {
-        if let Some(env_var) = app.config.get(&variable_name) {
-            if let Some(env_value) = env_var.values.get(value_index) {
-                let command =
-                    output::generate_powershell_command(&variable_name, &env_value.value);
-                println!("{}", command);
+        eprintln!("Error: {}", err);
+    }
+    result
+}
+
+/// Resolves the configuration file for the given target directory.
+fn target_config_path(target: EditTarget) -> Option<PathBuf> {
+    let dir = match target {
+        EditTarget::Local => env::current_dir().ok(),
+        EditTarget::Global => dirs::home_dir(),
+    };
+    dir.map(|d| config::config_file_in(&d))
+}
+
+/// Adds the current values of the given environment variables to the config file at `path`.
+fn add_values_from_env(path: &Path, variables: &[String], messages: &I18nMessages) {
+    let mut doc = match ConfigDocument::open(path) {
+        Ok(doc) => doc,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let label = messages.get("from_env_label");
+    for variable in variables {
+        match env::var(variable) {
+            Ok(value) => {
+                if let Err(err) = doc.add_value(variable, label, &value) {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
             }
+            Err(_) => eprintln!(
+                "{}",
+                messages
+                    .get("from_env_not_set")
+                    .replace("{variable}", variable)
+            ),
         }
     }
+    save_document(&doc);
+}
 
-    Ok(())
+/// Asks a yes/no question on stderr and reads the answer from stdin.
+fn ask_yes_no(prompt: &str, default: bool) -> bool {
+    eprint!("{}", prompt);
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" => true,
+        _ => false,
+    }
 }
 
-/// Handles the `edit` subcommand logic.
-fn handle_edit_command(target: EditTarget, messages: &I18nMessages) {
-    let path = match target {
-        EditTarget::Local => env::current_dir().ok().map(|p| p.join(".env.swap.toml")),
-        EditTarget::Global => dirs::home_dir().map(|p| p.join(".env.swap.toml")),
-    };
-
-    if let Some(path) = path {
-        // If the file doesn't exist, create it.
-        if !path.exists() {
-            if let Err(e) = fs::write(&path, "") {
-                let error_message = messages
-                    .get("file_creation_failed")
-                    .replace("{path}", path.to_str().unwrap_or(""));
-                eprintln!("{} ({})", error_message, e);
-                std::process::exit(1);
-            }
+/// Opens the local or global configuration file for editing, exiting on failure.
+fn open_target_document(global: bool) -> ConfigDocument {
+    let target = if global {
+        EditTarget::Global
+    } else {
+        EditTarget::Local
+    };
+    let Some(path) = target_config_path(target) else {
+        eprintln!("Could not determine the path for the configuration file.");
+        std::process::exit(1);
+    };
+    match ConfigDocument::open(&path) {
+        Ok(doc) => doc,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
         }
+    }
+}
 
-        // Open the file with the default associated application.
-        if let Err(e) = open::that(&path) {
-            eprintln!("Failed to open file at {:?}: {}", path, e);
+/// Saves an edited configuration file, exiting on failure.
+fn save_document(doc: &ConfigDocument) {
+    keep_allowed(doc.path(), || {
+        if let Err(err) = doc.save() {
+            eprintln!("Error: {}", err);
             std::process::exit(1);
         }
+    });
+}
+
+/// Handles the `edit` subcommand logic.
+fn handle_edit_command(args: EditArgs, messages: &I18nMessages) {
+    if let Some(path) = target_config_path(args.target) {
+        // The user is editing their own file, so it stays allowed afterwards.
+        keep_allowed(&path, || edit_config_file(&path, &args.from_env, messages));
     } else {
         // This case should be rare (e.g., home directory not found).
         eprintln!("Could not determine the path for the configuration file.");
@@ -112,56 +763,558 @@ fn handle_edit_command(target: EditTarget, messages: &I18nMessages) {
     }
 }
 
-/// Handles the `show` subcommand logic.
-fn handle_show_command(args: ShowArgs, messages: &I18nMessages) {
-    let config = match config::load_config() {
-        Ok(config) => config,
-        Err(err) => {
-            if err.contains("No .env.swap.toml file found") {
-                eprintln!("{}", messages.get("config_not_found"));
-            }
-            eprintln!("Error: {}", err);
+/// Creates the config file at `path` if needed and opens it in the editor until it is valid.
+fn edit_config_file(path: &Path, from_env: &[String], messages: &I18nMessages) {
+    // If the file doesn't exist, create it from the commented starter template.
+    if !path.exists()
+        && let Err(e) = permissions::write_private(path, messages.get("config_template").as_bytes())
+    {
+        let error_message = messages
+            .get("file_creation_failed")
+            .replace("{path}", path.to_str().unwrap_or(""));
+        eprintln!("{} ({})", error_message, e);
+        std::process::exit(1);
+    }
+
+    if !from_env.is_empty() {
+        add_values_from_env(path, from_env, messages);
+    }
+
+    // A broken config must not stop us from finding the editor to fix it with,
+    // so only the settings are read and errors elsewhere in the file are ignored.
+    let settings = config::read_home_settings().unwrap_or_else(|err| {
+        eprintln!("Warning: {}", err);
+        config::Settings::default()
+    });
+
+    let Some(command) = editor::find_editor(&settings) else {
+        // Without a configured editor, fall back to the default associated application.
+        if let Err(e) = open::that(path) {
+            eprintln!("Failed to open file at {:?}: {}", path, e);
             std::process::exit(1);
         }
+        return;
     };
 
-    if config.is_empty() {
-        eprintln!("{}", messages.get("config_not_found"));
+    // Like `crontab -e`, keep re-opening the file until it parses or the user gives up.
+    loop {
+        match editor::open_and_wait(&command, path) {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                eprintln!("Editor {:?} exited with {}", command[0], status);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to launch editor {:?}: {}", command[0], e);
+                std::process::exit(1);
+            }
+        }
+
+        match config::check_config_file(path) {
+            Ok(()) => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                if !ask_yes_no(messages.get("edit_reopen_prompt"), true) {
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Handles the `set` subcommand logic.
+fn handle_set_command(args: SetArgs, messages: &I18nMessages) {
+    ensure_stdout_is_captured(args.force_stdout, messages);
+    let loaded = load_config_or_exit(messages);
+    let result = set_variable(&args, &loaded, messages);
+
+    // Wipe the loaded values now, since exiting the process would skip their destructors.
+    drop(loaded);
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Handles the `undo` subcommand.
+fn handle_undo_command(args: UndoArgs, messages: &I18nMessages) {
+    ensure_stdout_is_captured(args.force_stdout, messages);
+    let loaded = load_config_or_exit(messages);
+    let result = emit_undo(&loaded, args.yes, messages);
+
+    // Wipe the loaded values now, since exiting the process would skip their destructors.
+    drop(loaded);
+    if let Err(err) = result {
+        eprintln!("{}", err);
         std::process::exit(1);
     }
+}
+
+/// Prints the command that sets `args.variable` to the value labeled `args.label`.
+fn set_variable(
+    args: &SetArgs,
+    loaded: &config::LoadedConfig,
+    messages: &I18nMessages,
+) -> Result<(), String> {
+    let config = &loaded.variables;
+    let Some(env_var) = config.get(&args.variable) else {
+        return Err(messages
+            .get("variable_not_defined")
+            .replace("{variable}", &args.variable));
+    };
+    let Some(env_value) = config::find_value(config, &args.variable, &args.label) else {
+        let labels: Vec<_> = env_var.values.iter().map(|v| v.label.as_str()).collect();
+        return Err(messages
+            .get("label_not_defined")
+            .replace("{variable}", &args.variable)
+            .replace("{label}", &args.label)
+            .replace("{labels}", &labels.join(", ")));
+    };
+
+    if env_value.confirm && !args.yes {
+        return Err(messages
+            .get("confirm_required")
+            .replace("{variable}", &args.variable)
+            .replace("{label}", &env_value.label));
+    }
+
+    emit_assignments(&[(&args.variable, env_value)], loaded, messages)
+        .map_err(|err| format!("Error: {}", err))
+}
+
+/// Handles the `show` subcommand logic.
+fn handle_show_command(args: ShowArgs, messages: &I18nMessages) {
+    let loaded = load_config_or_exit(messages);
+    if args.reveal && !loaded.settings.allows_reveal() {
+        drop(loaded);
+        eprintln!("{}", messages.get("reveal_disabled"));
+        std::process::exit(1);
+    }
+    let config = loaded.variables;
 
     let mut keys: Vec<_> = config.keys().cloned().collect();
     keys.sort();
+    let session = SessionState::from_env().unwrap_or_default();
 
     for key in keys {
-        let current_value = env::var(&key);
-        let status = match current_value {
-            Ok(val) => {
-                if args.reveal {
-                    val
-                } else {
-                    config
-                        .get(&key)
-                        .unwrap()
-                        .values
-                        .iter()
-                        .find(|v| v.value == val)
-                        .map_or_else(|| format!("({})", messages.get("status_custom_value")), |v| format!("<{}>", v.label))
-                }
-            }
-            Err(_) => {
-                if args.reveal {
-                    "".to_string() // Show empty if not set and revealing
-                } else {
-                    format!("({})", messages.get("status_not_set"))
-                }
-            }
+        let current_value = env::var(&key).ok();
+        let status = if args.reveal {
+            current_value.unwrap_or_default() // Show empty if not set and revealing
+        } else {
+            state::variable_status(&config, &session, &key, current_value.as_deref())
+                .describe(messages)
         };
         eprintln!("{}: {}", key, status);
     }
 
-    if !args.reveal {
+    if !args.reveal && loaded.settings.allows_reveal() {
         eprintln!();
         eprintln!("{}", messages.get("show_reveal_hint"));
     }
 }
+
+/// Handles the `history` subcommand logic.
+fn handle_history_command(args: HistoryArgs, messages: &I18nMessages) {
+    let entries = match History::open().and_then(|history| history.read()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| {
+            args.var
+                .as_ref()
+                .is_none_or(|var| entry.variable.eq_ignore_ascii_case(var))
+        })
+        .collect();
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&entries)
+            .expect("history entries are always serializable");
+        println!("{}", json);
+        return;
+    }
+    if entries.is_empty() {
+        eprintln!("{}", messages.get("history_empty"));
+        return;
+    }
+    for entry in entries {
+        let mut value = match (&entry.label, &entry.hash) {
+            (Some(label), _) => label.clone(),
+            (None, Some(_)) => format!("({})", messages.get("status_custom_value")),
+            (None, None) => format!("({})", messages.get("status_not_set")),
+        };
+        if entry.undo {
+            value = format!("{} {}", value, messages.get("history_undo"));
+        }
+        println!(
+            "{}",
+            messages
+                .get("history_entry")
+                .replace("{time}", &entry.time)
+                .replace("{variable}", &entry.variable)
+                .replace("{value}", &value)
+                .replace("{cwd}", &entry.cwd)
+        );
+    }
+}
+
+/// Handles the `import` subcommand logic.
+fn handle_import_command(args: ImportArgs, messages: &I18nMessages) {
+    let content = match fs::read_to_string(&args.file) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read {:?}: {}", args.file, e);
+            std::process::exit(1);
+        }
+    };
+    let entries = match dotenv::parse(&content) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Failed to parse {:?}: {}", args.file, err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut doc = open_target_document(args.global);
+    let result = entries.iter().try_fold(0, |added, (key, value)| {
+        doc.add_value(key, &args.label, value)
+            .map(|is_new| if is_new { added + 1 } else { added })
+    });
+
+    match result {
+        Ok(added) => {
+            save_document(&doc);
+            eprintln!(
+                "{}",
+                messages
+                    .get("import_summary")
+                    .replace("{added}", &added.to_string())
+                    .replace("{skipped}", &(entries.len() - added).to_string())
+                    .replace("{path}", &doc.path().display().to_string())
+            );
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles the `add` subcommand logic.
+fn handle_add_command(args: AddArgs, messages: &I18nMessages) {
+    let mut doc = open_target_document(args.global);
+
+    // Read the value without echoing it, so it never ends up in shell history.
+    let value = if args.value_stdin {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).map(|_| {
+            let len = input.trim_end_matches(['\r', '\n']).len();
+            input.truncate(len);
+            input
+        })
+    } else {
+        rpassword::prompt_password(
+            messages
+                .get("value_prompt")
+                .replace("{variable}", &args.variable)
+                .replace("{label}", &args.label),
+        )
+    };
+    let value = match value {
+        Ok(value) => Secret::from(value),
+        Err(e) => {
+            eprintln!("Failed to read the value: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match doc.add_value(&args.variable, &args.label, value.expose()) {
+        Ok(true) => {
+            save_document(&doc);
+            eprintln!("{}", messages.get("value_added"));
+        }
+        Ok(false) => eprintln!("{}", messages.get("value_already_exists")),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles the `remove` subcommand logic.
+fn handle_remove_command(args: RemoveArgs, messages: &I18nMessages) {
+    let mut doc = open_target_document(args.global);
+
+    let removed = match &args.label {
+        Some(label) => doc.remove_value(&args.variable, label),
+        None => Ok(usize::from(doc.remove_variable(&args.variable))),
+    };
+
+    match removed {
+        Ok(0) => {
+            eprintln!("{}", messages.get("nothing_to_remove"));
+            std::process::exit(1);
+        }
+        Ok(count) => {
+            save_document(&doc);
+            eprintln!(
+                "{}",
+                messages
+                    .get("values_removed")
+                    .replace("{count}", &count.to_string())
+            );
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles the `rename-label` subcommand logic.
+fn handle_rename_label_command(args: RenameLabelArgs, messages: &I18nMessages) {
+    let mut doc = open_target_document(args.global);
+
+    match doc.rename_label(&args.variable, &args.old, &args.new) {
+        Ok(0) => {
+            eprintln!("{}", messages.get("label_not_found"));
+            std::process::exit(1);
+        }
+        Ok(count) => {
+            save_document(&doc);
+            eprintln!(
+                "{}",
+                messages
+                    .get("labels_renamed")
+                    .replace("{count}", &count.to_string())
+            );
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the settings of the home configuration, which is the only place they come from.
+fn load_home_settings() -> config::Settings {
+    match config::load_config(|_, _| false) {
+        Ok(loaded) => loaded.settings,
+        Err(err) if err.contains("No .env.swap.toml file found") => config::Settings::default(),
+        Err(err) => {
+            eprintln!("Error loading config: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds the keyring from the home configuration's settings.
+fn load_keyring(messages: &I18nMessages) -> Keyring {
+    Keyring::from_settings(&load_home_settings(), messages)
+}
+
+/// Opens the history log if `history = true` is set in `[settings]`.
+fn open_history(settings: &config::Settings) -> Result<Option<History>, String> {
+    if settings.history {
+        History::open().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Rewrites values from the `from` key to the `to` key of the target file and reports the count.
+fn convert_target_values<F>(
+    global: bool,
+    variable: Option<&str>,
+    label: Option<&str>,
+    (from, to): (&str, &str),
+    convert: F,
+    messages: &I18nMessages,
+) where
+    F: FnMut(&str) -> Result<String, String>,
+{
+    let mut doc = open_target_document(global);
+
+    match doc.convert_values(variable, label, from, to, convert) {
+        Ok(0) => {
+            eprintln!("{}", messages.get("nothing_to_convert"));
+            std::process::exit(1);
+        }
+        Ok(count) => {
+            save_document(&doc);
+            eprintln!(
+                "{}",
+                messages
+                    .get("values_converted")
+                    .replace("{count}", &count.to_string())
+                    .replace("{path}", &doc.path().display().to_string())
+            );
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles the `encrypt` subcommand logic.
+fn handle_encrypt_command(args: CryptArgs, messages: &I18nMessages) {
+    let keyring = load_keyring(messages);
+    convert_target_values(
+        args.global,
+        args.variable.as_deref(),
+        args.label.as_deref(),
+        ("value", "value_enc"),
+        |plaintext| keyring.encrypt(plaintext),
+        messages,
+    );
+}
+
+/// Handles the `decrypt` subcommand logic.
+fn handle_decrypt_command(args: CryptArgs, messages: &I18nMessages) {
+    let keyring = load_keyring(messages);
+    convert_target_values(
+        args.global,
+        args.variable.as_deref(),
+        args.label.as_deref(),
+        ("value_enc", "value"),
+        |armored| {
+            keyring
+                .decrypt(armored)
+                .map(|plaintext| plaintext.expose().to_string())
+        },
+        messages,
+    );
+}
+
+/// Handles the `rekey` subcommand logic.
+fn handle_rekey_command(args: RekeyArgs, messages: &I18nMessages) {
+    let keyring = load_keyring(messages);
+    convert_target_values(
+        args.global,
+        None,
+        None,
+        ("value_enc", "value_enc"),
+        |armored| {
+            keyring
+                .decrypt(armored)
+                .and_then(|plaintext| keyring.encrypt(plaintext.expose()))
+        },
+        messages,
+    );
+}
+
+/// Returns the config file named by `path`, or the one in the current directory.
+fn allow_target(path: Option<PathBuf>) -> PathBuf {
+    match path.or_else(|| target_config_path(EditTarget::Local)) {
+        Some(path) => path,
+        None => {
+            eprintln!("Could not determine the path for the configuration file.");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the allowlist, applies `update` to it and saves it, exiting on failure.
+fn update_allow_list<T>(update: impl FnOnce(&mut AllowList) -> Result<T, String>) -> T {
+    let result = AllowList::load().and_then(|mut list| {
+        let value = update(&mut list)?;
+        list.save().map(|()| value)
+    });
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handles the `allow` subcommand logic.
+fn handle_allow_command(args: AllowArgs, messages: &I18nMessages) {
+    let path = allow_target(args.path);
+    if let Err(err) = config::check_config_file(&path) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+    update_allow_list(|list| list.allow(&path));
+    eprintln!(
+        "{}",
+        messages
+            .get("config_allowed")
+            .replace("{path}", &path.display().to_string())
+    );
+}
+
+/// Handles the `deny` subcommand logic.
+fn handle_deny_command(args: AllowArgs, messages: &I18nMessages) {
+    let path = allow_target(args.path);
+    let key = if update_allow_list(|list| list.deny(&path)) {
+        "config_denied"
+    } else {
+        "config_was_not_allowed"
+    };
+    eprintln!(
+        "{}",
+        messages
+            .get(key)
+            .replace("{path}", &path.display().to_string())
+    );
+}
+
+/// Handles the `fix-permissions` subcommand logic.
+fn handle_fix_permissions_command(messages: &I18nMessages) {
+    let mut paths: Vec<PathBuf> = [EditTarget::Local, EditTarget::Global]
+        .into_iter()
+        .filter_map(target_config_path)
+        .filter(|path| path.exists())
+        .collect();
+    paths.dedup();
+
+    let mut unfixable = false;
+    for path in &paths {
+        let problems = match permissions::check(path) {
+            Ok(problems) => problems,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        };
+        for problem in &problems {
+            match problem {
+                PermissionProblem::TooOpen(_) => {
+                    if let Err(err) = permissions::fix(path) {
+                        eprintln!("Error: {}", err);
+                        std::process::exit(1);
+                    }
+                    eprintln!(
+                        "{}",
+                        messages
+                            .get("permissions_fixed")
+                            .replace("{path}", &path.display().to_string())
+                    );
+                }
+                PermissionProblem::OtherOwner(_) => {
+                    eprintln!("{}", describe_permission_problem(path, problem, messages));
+                    unfixable = true;
+                }
+            }
+        }
+        if problems.is_empty() {
+            eprintln!(
+                "{}",
+                messages
+                    .get("permissions_ok")
+                    .replace("{path}", &path.display().to_string())
+            );
+        }
+    }
+
+    if unfixable {
+        std::process::exit(1);
+    }
+}
@@ -4,7 +4,9 @@
 ///
 /// This function takes a variable name and a value, and returns a string
 /// formatted as a PowerShell command. It handles escaping of single quotes
-/// in the value, which is crucial for correctness in PowerShell.
+/// in the value, which is crucial for correctness in PowerShell. PowerShell
+/// also ends single-quoted strings at the typographic quotes `‘ ’ ‚ ‛`, so
+/// those are doubled as well.
 ///
 /// # Arguments
 ///
@@ -22,10 +24,39 @@
 /// ```
 pub fn generate_powershell_command(name: &str, value: &str) -> String {
     // In PowerShell, single quotes within a single-quoted string are escaped by doubling them.
-    let escaped_value = value.replace('\'', "''");
+    let mut escaped_value = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            escaped_value.push(c);
+        }
+        escaped_value.push(c);
+    }
     format!("$Env:{} = '{}'", name, escaped_value)
 }
 
+/// Generates a PowerShell command that removes an environment variable.
+///
+/// Removing a variable that is not set is not an error.
+pub fn generate_powershell_unset_command(name: &str) -> String {
+    format!("Remove-Item Env:{} -ErrorAction SilentlyContinue", name)
+}
+
+/// Generates the PowerShell snippet that defines the `envswap` wrapper function.
+///
+/// Adding `gnb-envswap init | Out-String | Invoke-Expression` to `$PROFILE` defines
+/// it in every session, for setups not installed with Scoop.
+pub fn generate_powershell_init() -> String {
+    "function envswap { gnb-envswap @args | Invoke-Expression }".to_string()
+}
+
+/// Returns whether commands may be printed to stdout.
+///
+/// They are refused when stdout is a terminal, where the value would only be shown
+/// on screen instead of being set, unless `--force-stdout` was given.
+pub fn can_print_commands(force_stdout: bool, stdout_is_terminal: bool) -> bool {
+    force_stdout || !stdout_is_terminal
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,6 +79,21 @@ mod tests {
         assert_eq!(command, "$Env:MESSAGE = 'Here''s Johnny''s car!'");
     }
 
+    #[test]
+    fn test_generate_powershell_command_with_typographic_quotes() {
+        let command = generate_powershell_command("MESSAGE", "‘a’ ‚b‛");
+        assert_eq!(command, "$Env:MESSAGE = '‘‘a’’ ‚‚b‛‛'");
+    }
+
+    #[test]
+    fn test_generate_powershell_command_with_quote_in_label() {
+        let mut state = crate::state::SessionState::parse("", "salt".to_string());
+        state.record("API_KEY", "Bob’s key", None, None, "k");
+        let command = generate_powershell_command(crate::state::STATE_VARIABLE, &state.to_json());
+        assert!(command.contains("Bob’’s key"));
+        assert_eq!(command.matches('\'').count(), 2);
+    }
+
     #[test]
     fn test_generate_powershell_command_empty_value() {
         let command = generate_powershell_command("EMPTY_VAR", "");
@@ -59,4 +105,29 @@ mod tests {
         let command = generate_powershell_command("NO_SPECIAL_CHARS", "azAZ09-_. /");
         assert_eq!(command, "$Env:NO_SPECIAL_CHARS = 'azAZ09-_. /'");
     }
+
+    #[test]
+    fn test_can_print_commands() {
+        assert!(can_print_commands(false, false));
+        assert!(!can_print_commands(false, true));
+        // --force-stdout bypasses the check.
+        assert!(can_print_commands(true, true));
+        assert!(can_print_commands(true, false));
+    }
+
+    #[test]
+    fn test_generate_powershell_init_passes_arguments() {
+        let snippet = generate_powershell_init();
+        assert!(snippet.starts_with("function envswap {"));
+        assert!(snippet.contains("gnb-envswap @args | Invoke-Expression"));
+    }
+
+    #[test]
+    fn test_generate_powershell_unset_command() {
+        let command = generate_powershell_unset_command("MY_VAR");
+        assert_eq!(
+            command,
+            "Remove-Item Env:MY_VAR -ErrorAction SilentlyContinue"
+        );
+    }
 }
@@ -0,0 +1,152 @@
+//! Checks that config files holding secrets are private to their owner.
+//!
+//! Only Unix file modes and owners are checked. On other platforms every
+//! file is considered safe.
+
+use std::path::Path;
+
+/// Something that lets other users read or change a config file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PermissionProblem {
+    /// Group or other users can read or write the file. Holds the file's permission bits.
+    TooOpen(u32),
+    /// The file belongs to another user. Holds the owner's user id.
+    OtherOwner(u32),
+}
+
+impl PermissionProblem {
+    /// Describes the problem, for error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            PermissionProblem::TooOpen(mode) => {
+                format!("is accessible by other users (mode {:o})", mode)
+            }
+            PermissionProblem::OtherOwner(uid) => format!("is owned by another user (uid {})", uid),
+        }
+    }
+}
+
+/// Returns the problems with the permissions of the file at `path`.
+#[cfg(unix)]
+pub fn check(path: &Path) -> Result<Vec<PermissionProblem>, String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::geteuid() };
+    Ok(problems(metadata.mode() & 0o7777, metadata.uid(), uid))
+}
+
+/// Returns the problems with the permissions of the file at `path`.
+#[cfg(not(unix))]
+pub fn check(_path: &Path) -> Result<Vec<PermissionProblem>, String> {
+    Ok(Vec::new())
+}
+
+/// Finds the problems with a file of the given mode and owner, as seen by `current_uid`.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn problems(mode: u32, owner: u32, current_uid: u32) -> Vec<PermissionProblem> {
+    let mut problems = Vec::new();
+    if mode & 0o066 != 0 {
+        problems.push(PermissionProblem::TooOpen(mode));
+    }
+    if owner != current_uid {
+        problems.push(PermissionProblem::OtherOwner(owner));
+    }
+    problems
+}
+
+/// Removes group and other access from the file at `path`.
+///
+/// Ownership can't be fixed this way; the caller reports it instead.
+#[cfg(unix)]
+pub fn fix(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let mut permissions = metadata.permissions();
+    permissions.set_mode(permissions.mode() & 0o7700);
+    std::fs::set_permissions(path, permissions)
+        .map_err(|e| format!("Failed to change the permissions of {:?}: {}", path, e))
+}
+
+/// Removes group and other access from the file at `path`.
+#[cfg(not(unix))]
+pub fn fix(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// Writes `content` to the file at `path`, replacing what it held.
+///
+/// A new file is created readable by its owner only, so the files we create
+/// pass [`check`]; an existing file keeps its permissions.
+pub fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        assert!(problems(0o600, 1000, 1000).is_empty());
+        assert!(problems(0o700, 1000, 1000).is_empty());
+        assert_eq!(
+            problems(0o644, 1000, 1000),
+            [PermissionProblem::TooOpen(0o644)]
+        );
+        assert_eq!(
+            problems(0o602, 0, 1000),
+            [
+                PermissionProblem::TooOpen(0o602),
+                PermissionProblem::OtherOwner(0)
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_and_fix() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert_eq!(check(&path).unwrap(), [PermissionProblem::TooOpen(0o644)]);
+        fix(&path).unwrap();
+        assert!(check(&path).unwrap().is_empty());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_keeps_existing_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.swap.toml");
+        write_private(&path, b"[API_KEY]\n").unwrap();
+        assert!(check(&path).unwrap().is_empty());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        write_private(&path, b"").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
@@ -0,0 +1,273 @@
+//! Resolves the actual value of a selected `EnvValue`.
+//!
+//! Values that live outside the config file are only read here, after the
+//! user has picked them, so unused secrets are never loaded.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use wait_timeout::ChildExt;
+
+use crate::config::EnvValue;
+use crate::crypto::Keyring;
+use crate::secret::Secret;
+
+/// How long a `value_cmd` may run before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns the value to set for `value`, reading or decrypting it from its source if needed.
+pub fn resolve_value(value: &EnvValue, keyring: &Keyring) -> Result<Secret, String> {
+    if let Some(inline) = &value.value {
+        return Ok(inline.clone());
+    }
+    if let Some(file) = &value.value_file {
+        return read_value_file(file, value.source.as_deref()).map(Secret::from);
+    }
+    if let Some(command) = &value.value_cmd {
+        return run_value_cmd(command, value.source.as_deref(), COMMAND_TIMEOUT).map(Secret::from);
+    }
+    if let Some(armored) = &value.value_enc {
+        return keyring.decrypt(armored);
+    }
+    Err(format!("'{}' has no value", value.label))
+}
+
+/// Runs a `value_cmd` and returns its standard output, minus a single trailing newline.
+///
+/// The command is started directly, never through a shell, from the directory of
+/// the config file it was defined in. Stdin is inherited so that tools like `gpg`
+/// can still ask for a passphrase.
+fn run_value_cmd(
+    command: &[String],
+    config_path: Option<&Path>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "value_cmd is empty".to_string())?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = config_path.and_then(|p| p.parent()) {
+        cmd.current_dir(dir);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to run value_cmd {:?}: {}", program, e))?;
+
+    // Drain the pipes on separate threads so a chatty command can't fill them and block.
+    let stdout = child.stdout.take().map(spawn_reader);
+    let stderr = child.stderr.take().map(spawn_reader);
+
+    let status = match child.wait_timeout(timeout) {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "value_cmd {:?} timed out after {} seconds",
+                program,
+                timeout.as_secs()
+            ));
+        }
+        Err(e) => return Err(format!("Failed to wait for value_cmd {:?}: {}", program, e)),
+    };
+
+    let stdout = stdout.map(join_reader).unwrap_or_default();
+    let stderr = stderr.map(join_reader).unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(format!(
+            "value_cmd {:?} failed ({}): {}",
+            program,
+            status,
+            stderr.trim()
+        ));
+    }
+
+    let mut output = String::from_utf8(stdout)
+        .map_err(|_| format!("value_cmd {:?} printed invalid UTF-8", program))?;
+    trim_trailing_newline(&mut output);
+    Ok(output)
+}
+
+/// Reads a pipe to the end on a background thread.
+fn spawn_reader<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        pipe.read_to_end(&mut buf).map(|_| buf)
+    })
+}
+
+/// Collects the output of a `spawn_reader` thread, treating failures as no output.
+fn join_reader(handle: thread::JoinHandle<io::Result<Vec<u8>>>) -> Vec<u8> {
+    handle.join().ok().and_then(|r| r.ok()).unwrap_or_default()
+}
+
+/// Removes a single trailing `\n` or `\r\n`.
+fn trim_trailing_newline(s: &mut String) {
+    if s.ends_with('\n') {
+        s.pop();
+        if s.ends_with('\r') {
+            s.pop();
+        }
+    }
+}
+
+/// Reads a `value_file`, dropping a single trailing newline.
+fn read_value_file(file: &str, config_path: Option<&Path>) -> Result<String, String> {
+    let path = resolve_path(file, config_path);
+    let mut content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read value_file {:?}: {}", path, e))?;
+    trim_trailing_newline(&mut content);
+    Ok(content)
+}
+
+/// Expands a leading `~` and resolves relative paths against the config file's directory.
+pub fn resolve_path(file: &str, config_path: Option<&Path>) -> PathBuf {
+    let expanded = match file.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => dirs::home_dir()
+            .map(|home| home.join(rest.trim_start_matches(['/', '\\'])))
+            .unwrap_or_else(|| PathBuf::from(file)),
+        _ => PathBuf::from(file),
+    };
+
+    match config_path.and_then(|p| p.parent()) {
+        Some(dir) if expanded.is_relative() => dir.join(expanded),
+        _ => expanded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring() -> Keyring {
+        let messages = crate::i18n::load_messages().unwrap();
+        Keyring::from_settings(&Default::default(), &messages)
+    }
+
+    #[test]
+    fn test_resolve_inline_value() {
+        let value = EnvValue {
+            label: "Dev".to_string(),
+            value: Some("dev-key".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_value(&value, &keyring()).unwrap().expose(),
+            "dev-key"
+        );
+    }
+
+    #[test]
+    fn test_resolve_value_file_relative_to_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("keys")).unwrap();
+        fs::write(dir.path().join("keys/prod.pem"), "-----BEGIN-----\nabc\n").unwrap();
+
+        let value = EnvValue {
+            label: "Prod".to_string(),
+            value_file: Some("keys/prod.pem".to_string()),
+            source: Some(dir.path().join(".env.swap.toml")),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_value(&value, &keyring()).unwrap().expose(),
+            "-----BEGIN-----\nabc"
+        );
+    }
+
+    #[test]
+    fn test_resolve_value_file_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let value = EnvValue {
+            label: "Prod".to_string(),
+            value_file: Some("missing.pem".to_string()),
+            source: Some(dir.path().join(".env.swap.toml")),
+            ..Default::default()
+        };
+        let err = resolve_value(&value, &keyring()).unwrap_err();
+        assert!(err.starts_with("Failed to read value_file"));
+        assert!(err.contains("missing.pem"));
+    }
+
+    #[cfg(unix)]
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_value_cmd_trims_newline_and_skips_shell() {
+        let output = run_value_cmd(&sh("printf 'secret\\n'"), None, COMMAND_TIMEOUT).unwrap();
+        assert_eq!(output, "secret");
+
+        // Arguments are passed verbatim, without shell interpolation.
+        let command = vec!["echo".to_string(), "$HOME `id`".to_string()];
+        let output = run_value_cmd(&command, None, COMMAND_TIMEOUT).unwrap();
+        assert_eq!(output, "$HOME `id`");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_value_cmd_runs_in_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("token"), "from-dir").unwrap();
+        let config = dir.path().join(".env.swap.toml");
+        let output = run_value_cmd(&sh("cat token"), Some(&config), COMMAND_TIMEOUT).unwrap();
+        assert_eq!(output, "from-dir");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_value_cmd_failure_includes_stderr() {
+        let err =
+            run_value_cmd(&sh("echo 'not found' >&2; exit 3"), None, COMMAND_TIMEOUT).unwrap_err();
+        assert!(err.contains("failed"));
+        assert!(err.ends_with("not found"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_value_cmd_timeout() {
+        let err = run_value_cmd(&sh("sleep 5"), None, Duration::from_millis(100)).unwrap_err();
+        assert!(err.contains("timed out"));
+    }
+
+    #[test]
+    fn test_run_value_cmd_missing_program() {
+        let command = vec!["gnb-envswap-no-such-program".to_string()];
+        let err = run_value_cmd(&command, None, COMMAND_TIMEOUT).unwrap_err();
+        assert!(err.starts_with("Failed to run value_cmd"));
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let config = Path::new("/work/.env.swap.toml");
+        assert_eq!(
+            resolve_path("keys/a.pem", Some(config)),
+            PathBuf::from("/work/keys/a.pem")
+        );
+        assert_eq!(
+            resolve_path("/abs/a.pem", Some(config)),
+            PathBuf::from("/abs/a.pem")
+        );
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(resolve_path("~/a.pem", Some(config)), home.join("a.pem"));
+        }
+        // `~user` style paths are not expanded.
+        assert_eq!(
+            resolve_path("~other/a.pem", Some(config)),
+            PathBuf::from("/work/~other/a.pem")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! A string that holds a secret value.
+
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// A secret string that is wiped from memory when dropped.
+///
+/// Its `Debug` output is redacted, so secrets can't end up in logs or panic
+/// messages by accident. Use [`Secret::expose`] to read the value.
+#[derive(Clone, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Returns the secret value.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the underlying string, for filling it in place.
+    ///
+    /// Growing it past its capacity leaves the old buffer behind unwiped;
+    /// use [`Secret::push_str`] to append instead.
+    pub fn expose_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+
+    /// Appends `text`, moving into a larger buffer and wiping the old one
+    /// when the current one is full.
+    pub fn push_str(&mut self, text: &str) {
+        let needed = self.0.len() + text.len();
+        if needed > self.0.capacity() {
+            let mut grown = String::with_capacity(needed.max(self.0.capacity() * 2));
+            grown.push_str(&self.0);
+            // The old buffer is wiped when the replaced Secret is dropped.
+            drop(std::mem::replace(self, Secret(grown)));
+        }
+        self.0.push_str(text);
+    }
+
+    /// Appends `c`, like [`Secret::push_str`].
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+/// Accepts a string, or an integer or boolean as written, as YAML and JSON
+/// configs often leave ports and PINs unquoted.
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SecretVisitor)
+    }
+}
+
+struct SecretVisitor;
+
+impl Visitor<'_> for SecretVisitor {
+    type Value = Secret;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string, integer or boolean (quote other values)")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Secret, E> {
+        Ok(Secret::from(value))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Secret, E> {
+        Ok(Secret(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Secret, E> {
+        Ok(Secret(value.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Secret, E> {
+        Ok(Secret(value.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Secret, E> {
+        Ok(Secret(value.to_string()))
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(!format!("{:?}", secret).contains("hunter2"));
+        assert!(!format!("{:?}", Some(secret)).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let secret: Secret = serde_json::from_str("\"hunter2\"").unwrap();
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_push_grows_the_buffer() {
+        let mut secret = Secret::from(String::with_capacity(4));
+        secret.push_str("hunt");
+        secret.push('e');
+        secret.push_str("r2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(secret.expose_mut().capacity() >= 8);
+    }
+}
@@ -0,0 +1,274 @@
+//! Tracks what gnb-envswap has set in the current shell session.
+//!
+//! Every emitted command also sets `GNB_ENVSWAP_STATE`, a compact JSON object
+//! that maps each variable to the label and source file of the value it was
+//! set to, plus a short hash of that value. A variable still holds what we set
+//! as long as its current value matches the hash, even when several labels
+//! share a value or the value came from a file, command or decryption.
+//!
+//! The variable is inherited by every child process, so the hash is salted
+//! with the per-user salt from the data directory; without it, low-entropy
+//! values such as hostnames or PINs could be brute-forced from the hash.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::datadir;
+use crate::i18n::I18nMessages;
+
+/// The environment variable holding the session state.
+pub const STATE_VARIABLE: &str = "GNB_ENVSWAP_STATE";
+
+/// What a single variable was set to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateEntry {
+    #[serde(rename = "l")]
+    pub label: String,
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// For variables set through another value's `also` table, the variable that was selected.
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    #[serde(rename = "h")]
+    pub hash: String,
+}
+
+/// The variables set by gnb-envswap in this session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionState {
+    entries: BTreeMap<String, StateEntry>,
+    salt: String,
+}
+
+impl SessionState {
+    /// Reads the state from `GNB_ENVSWAP_STATE`. A missing or malformed state is empty.
+    pub fn from_env() -> Result<Self, String> {
+        let salt = datadir::salt()?;
+        let state = std::env::var(STATE_VARIABLE).unwrap_or_default();
+        Ok(Self::parse(&state, salt))
+    }
+
+    /// Parses a serialized state whose values were hashed with `salt`.
+    /// A malformed state is treated as empty.
+    pub fn parse(state: &str, salt: String) -> Self {
+        SessionState {
+            entries: serde_json::from_str(state).unwrap_or_default(),
+            salt,
+        }
+    }
+
+    /// Serializes the state for `GNB_ENVSWAP_STATE`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.entries).expect("state entries are always serializable")
+    }
+
+    /// Records that `variable` was set to `value`.
+    pub fn record(
+        &mut self,
+        variable: &str,
+        label: &str,
+        source: Option<&Path>,
+        via: Option<&str>,
+        value: &str,
+    ) {
+        self.entries.insert(
+            variable.to_string(),
+            StateEntry {
+                label: label.to_string(),
+                source: source.map(|p| p.display().to_string()),
+                via: via.map(str::to_string),
+                hash: self.hash_value(value),
+            },
+        );
+    }
+
+    /// Returns what `variable` was set to, if it still holds `current_value`.
+    pub fn entry(&self, variable: &str, current_value: &str) -> Option<&StateEntry> {
+        self.entries
+            .get(variable)
+            .filter(|entry| entry.hash == self.hash_value(current_value))
+    }
+
+    /// Returns a short salted hash identifying a value, without revealing it.
+    fn hash_value(&self, value: &str) -> String {
+        let mut hash = datadir::hash_value(&self.salt, value);
+        hash.truncate(16);
+        hash
+    }
+}
+
+/// The current status of a configured variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableStatus {
+    /// The variable holds the value with this label.
+    Label(String),
+    /// The variable holds a value that doesn't match any configured one.
+    Custom,
+    /// The variable is not set.
+    NotSet,
+}
+
+impl VariableStatus {
+    /// Describes the status the way `show` prints it.
+    pub fn describe(&self, messages: &I18nMessages) -> String {
+        match self {
+            VariableStatus::Label(label) => format!("<{}>", label),
+            VariableStatus::Custom => format!("({})", messages.get("status_custom_value")),
+            VariableStatus::NotSet => format!("({})", messages.get("status_not_set")),
+        }
+    }
+}
+
+/// Works out which configured value `variable` currently holds.
+///
+/// The session state is trusted first. Without it, the current value is
+/// compared against the inline values of the config.
+pub fn variable_status(
+    config: &Config,
+    state: &SessionState,
+    variable: &str,
+    current_value: Option<&str>,
+) -> VariableStatus {
+    let Some(current_value) = current_value else {
+        return VariableStatus::NotSet;
+    };
+    if let Some(entry) = state.entry(variable, current_value) {
+        return VariableStatus::Label(entry.label.clone());
+    }
+    config
+        .get(variable)
+        .and_then(|var| {
+            var.values.iter().find(|v| {
+                v.value
+                    .as_ref()
+                    .is_some_and(|v| v.expose() == current_value)
+            })
+        })
+        .map_or(VariableStatus::Custom, |v| {
+            VariableStatus::Label(v.label.clone())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EnvValue, EnvVar};
+    use std::collections::HashMap;
+
+    fn config() -> Config {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![
+                    EnvValue {
+                        label: "Dev".to_string(),
+                        value: Some("shared".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Staging".to_string(),
+                        value: Some("shared".into()),
+                        ..Default::default()
+                    },
+                    EnvValue {
+                        label: "Prod".to_string(),
+                        value_cmd: Some(vec!["pass".to_string()]),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = SessionState::parse("", "salt".to_string());
+        state.record(
+            "API_KEY",
+            "Prod",
+            Some(Path::new("/w/.env.swap.toml")),
+            None,
+            "k",
+        );
+        state.record("API_URL", "Prod", None, Some("API_KEY"), "https://x");
+
+        let json = state.to_json();
+        assert!(!json.contains("https://x"));
+        assert_eq!(SessionState::parse(&json, "salt".to_string()), state);
+        assert_eq!(
+            SessionState::parse("not json", String::new()),
+            SessionState::default()
+        );
+    }
+
+    #[test]
+    fn test_hash_is_salted() {
+        let mut state = SessionState::parse("", "salt".to_string());
+        state.record("PIN", "Mine", None, None, "1234");
+        let json = state.to_json();
+
+        // Hashed with another salt, the same value doesn't match.
+        let other = SessionState::parse(&json, "other".to_string());
+        assert!(other.entry("PIN", "1234").is_none());
+        assert!(
+            SessionState::parse(&json, "salt".to_string())
+                .entry("PIN", "1234")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_variable_status_prefers_state() {
+        let config = config();
+        let mut state = SessionState::default();
+
+        // Without state, the first value with a matching inline value wins.
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", Some("shared")),
+            VariableStatus::Label("Dev".to_string())
+        );
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", Some("other")),
+            VariableStatus::Custom
+        );
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", None),
+            VariableStatus::NotSet
+        );
+
+        state.record("API_KEY", "Staging", None, None, "shared");
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", Some("shared")),
+            VariableStatus::Label("Staging".to_string())
+        );
+
+        state.record("API_KEY", "Prod", None, None, "from-command");
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", Some("from-command")),
+            VariableStatus::Label("Prod".to_string())
+        );
+        // Changed by hand since: the state no longer applies.
+        assert_eq!(
+            variable_status(&config, &state, "API_KEY", Some("edited")),
+            VariableStatus::Custom
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let i18n = crate::i18n::load_messages().unwrap();
+        assert_eq!(
+            VariableStatus::Label("Prod".to_string()).describe(&i18n),
+            "<Prod>"
+        );
+        assert_eq!(
+            VariableStatus::NotSet.describe(&i18n),
+            format!("({})", i18n.get("status_not_set"))
+        );
+    }
+}
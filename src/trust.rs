@@ -0,0 +1,180 @@
+//! The allowlist of project-local config files.
+//!
+//! Like direnv, a config file found in the current directory is only used
+//! after the user has allowed it with `gnb-envswap allow`. Each allowed file
+//! is recorded with the SHA-256 hash of its contents, so any change to the
+//! file has to be allowed again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// Whether a config file may be used.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TrustStatus {
+    /// The file was allowed and has not changed since.
+    Allowed,
+    /// The file was allowed, but its contents have changed since.
+    Changed,
+    /// The file has never been allowed.
+    Unknown,
+}
+
+/// The allowed config files and the hashes of their allowed contents.
+pub struct AllowList {
+    file: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl AllowList {
+    /// Loads the allowlist from the user's data directory.
+    pub fn load() -> Result<Self, String> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| "Could not determine the user data directory.".to_string())?;
+        Self::load_from(dir.join("gnb-envswap").join("allowed.json"))
+    }
+
+    /// Loads the allowlist stored at `file`, which may not exist yet.
+    fn load_from(file: PathBuf) -> Result<Self, String> {
+        let entries = match fs::read_to_string(&file) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse the allowlist at {:?}: {}", file, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Failed to read the allowlist at {:?}: {}", file, e)),
+        };
+        Ok(AllowList { file, entries })
+    }
+
+    /// Writes the allowlist back to disk.
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| format!("Failed to write the allowlist: {}", e))?;
+        fs::write(&self.file, content + "\n")
+            .map_err(|e| format!("Failed to write the allowlist at {:?}: {}", self.file, e))
+    }
+
+    /// Checks whether the config file at `path` is allowed with its current contents.
+    pub fn status(&self, path: &Path) -> Result<TrustStatus, String> {
+        self.status_of(path, &read_file(path)?)
+    }
+
+    /// Checks whether the config file at `path` is allowed with `content`, already read from it.
+    ///
+    /// Checking the same bytes that are then parsed means the file can't be
+    /// swapped for another one in between.
+    pub fn status_of(&self, path: &Path, content: &[u8]) -> Result<TrustStatus, String> {
+        let Some(allowed_hash) = self.entries.get(&key(path)?) else {
+            return Ok(TrustStatus::Unknown);
+        };
+        if *allowed_hash == hash_content(content) {
+            Ok(TrustStatus::Allowed)
+        } else {
+            Ok(TrustStatus::Changed)
+        }
+    }
+
+    /// Allows the config file at `path` with its current contents.
+    pub fn allow(&mut self, path: &Path) -> Result<(), String> {
+        self.allow_content(path, &read_file(path)?)
+    }
+
+    /// Allows the config file at `path` with `content`, already read from it.
+    pub fn allow_content(&mut self, path: &Path, content: &[u8]) -> Result<(), String> {
+        self.entries.insert(key(path)?, hash_content(content));
+        Ok(())
+    }
+
+    /// Removes the config file at `path` from the allowlist. Returns whether it was allowed.
+    pub fn deny(&mut self, path: &Path) -> Result<bool, String> {
+        Ok(self.entries.remove(&key(path)?).is_some())
+    }
+}
+
+/// Returns the allowlist key of a config file: its absolute, canonical path.
+fn key(path: &Path) -> Result<String, String> {
+    let path =
+        fs::canonicalize(path).map_err(|e| format!("Failed to resolve {:?}: {}", path, e))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+/// Returns the hex-encoded SHA-256 hash of a file's contents.
+fn hash_content(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_and_detect_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("data/allowed.json");
+        let config = dir.path().join(".env.swap.toml");
+        fs::write(&config, "[API_KEY]\n").unwrap();
+
+        let mut list = AllowList::load_from(store.clone()).unwrap();
+        assert_eq!(list.status(&config).unwrap(), TrustStatus::Unknown);
+
+        list.allow(&config).unwrap();
+        list.save().unwrap();
+        let list = AllowList::load_from(store.clone()).unwrap();
+        assert_eq!(list.status(&config).unwrap(), TrustStatus::Allowed);
+
+        fs::write(&config, "[API_KEY]\nvalues = []\n").unwrap();
+        assert_eq!(list.status(&config).unwrap(), TrustStatus::Changed);
+    }
+
+    #[test]
+    fn test_deny() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(".env.swap.toml");
+        fs::write(&config, "").unwrap();
+
+        let mut list = AllowList::load_from(dir.path().join("allowed.json")).unwrap();
+        assert!(!list.deny(&config).unwrap());
+        list.allow(&config).unwrap();
+        assert!(list.deny(&config).unwrap());
+        assert_eq!(list.status(&config).unwrap(), TrustStatus::Unknown);
+    }
+
+    #[test]
+    fn test_hash_content() {
+        assert_eq!(
+            hash_content(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_status_of_checks_the_given_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(".env.swap.toml");
+        fs::write(&config, "[API_KEY]\n").unwrap();
+
+        let mut list = AllowList::load_from(dir.path().join("allowed.json")).unwrap();
+        list.allow_content(&config, b"[API_KEY]\n").unwrap();
+        // Only the content that was read counts, whatever the file holds by now.
+        fs::write(&config, "[EVIL]\n").unwrap();
+        assert_eq!(
+            list.status_of(&config, b"[API_KEY]\n").unwrap(),
+            TrustStatus::Allowed
+        );
+        assert_eq!(
+            list.status_of(&config, b"[EVIL]\n").unwrap(),
+            TrustStatus::Changed
+        );
+    }
+}
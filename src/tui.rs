@@ -1,20 +1,21 @@
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{
+    Terminal,
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
-    Terminal,
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
-use std::io::{self, stderr, Stderr};
+use std::io::{self, Stderr, stderr};
 use std::time::Duration;
 
-use crate::app::{App, SelectionPhase};
+use crate::app::{App, CustomStage, Revealed, SelectionPhase};
+use crate::config::{EnvValue, HOME_PREFIX, WORK_PREFIX};
 
 // A wrapper around the ratatui Terminal.
 pub struct Tui {
@@ -68,14 +69,13 @@ pub fn run_tui(app: &mut App) -> io::Result<()> {
 // Handle user input events.
 fn handle_events(app: &mut App) -> io::Result<()> {
     // Poll for an event with a timeout.
-    if event::poll(Duration::from_millis(250))? {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match app.current_phase {
-                    SelectionPhase::Variable => handle_variable_selection_keys(key, app),
-                    SelectionPhase::Value => handle_value_selection_keys(key, app),
-                }
-            }
+    if event::poll(Duration::from_millis(250))?
+        && let Event::Key(key) = event::read()?
+        && key.kind == KeyEventKind::Press
+    {
+        match app.current_phase {
+            SelectionPhase::Variable => handle_variable_selection_keys(key, app),
+            SelectionPhase::Value => handle_value_selection_keys(key, app),
         }
     }
     Ok(())
@@ -83,23 +83,42 @@ fn handle_events(app: &mut App) -> io::Result<()> {
 
 // Handle key presses during the variable selection phase.
 fn handle_variable_selection_keys(key: event::KeyEvent, app: &mut App) {
+    if app.confirming {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_selection(),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_confirmation(),
+            _ => {}
+        }
+        return;
+    }
+    if app.discarding {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_discard(),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.discarding = false,
+            _ => {}
+        }
+        return;
+    }
+
     match key.code {
-        KeyCode::Esc => app.should_quit = true,
+        // Quits, asking first if values chosen with Tab would be dropped.
+        KeyCode::Esc => app.request_quit(),
+        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => app.request_undo(),
+        // Set the values chosen with Tab without choosing another one.
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => app.apply_pending(),
         KeyCode::Up => app.previous_variable(),
         KeyCode::Down => app.next_variable(),
         KeyCode::Enter => {
-            if let Some(selected_index) = app.variable_list_state.selected() {
-                let items = app.filtered_variables();
-                if let Some(variable_name) = items.get(selected_index) {
-                    app.selected_variable = Some((*variable_name).clone());
-                    app.current_phase = SelectionPhase::Value;
-                    app.search_query.clear(); // Clear search for the next phase
-                    app.value_list_state.select(Some(0));
-                    app.adjust_selection();
-                }
+            let selected = app.variable_list_state.selected().and_then(|index| {
+                app.filtered_variables()
+                    .get(index)
+                    .map(|name| name.to_string())
+            });
+            if let Some(variable_name) = selected {
+                app.enter_variable(&variable_name);
             }
         }
-        KeyCode::Char(c) => {
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.search_query.push(c);
             app.adjust_selection();
         }
@@ -113,23 +132,40 @@ fn handle_variable_selection_keys(key: event::KeyEvent, app: &mut App) {
 
 // Handle key presses during the value selection phase.
 fn handle_value_selection_keys(key: event::KeyEvent, app: &mut App) {
-    match key.code {
-        KeyCode::Esc => {
-            app.current_phase = SelectionPhase::Variable;
-            app.selected_variable = None;
-            app.search_query.clear(); // Clear search when going back
-            app.value_list_state.select(None);
-            app.adjust_selection();
+    if app.confirming {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_selection(),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_confirmation(),
+            _ => {}
         }
+        return;
+    }
+    if app.custom.is_some() {
+        handle_custom_input_keys(key, app);
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc => app.leave_variable(),
         KeyCode::Up => app.previous_value(),
         KeyCode::Down => app.next_value(),
-        KeyCode::Enter => {
-            // Final selection is made, quit the TUI to output the command.
-            if app.value_list_state.selected().is_some() && !app.filtered_values().is_empty() {
-                app.should_quit = true;
-            }
+        // Final selection is made, quit the TUI to output the commands for it and the
+        // values chosen with Tab (after confirmation, for values marked with `confirm`).
+        KeyCode::Enter if app.is_custom_item_selected() => app.start_custom_input(),
+        KeyCode::Enter
+            if app.value_list_state.selected().is_some() && !app.filtered_values().is_empty() =>
+        {
+            app.request_selection();
+        }
+        // Show the highlighted value for a moment, in case labels alone don't tell values apart.
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.reveal_selected()
         }
-        KeyCode::Char(c) => {
+        // Choose this value too and go back to pick another variable.
+        KeyCode::Tab => app.toggle_pending(),
+        // Set the values chosen with Tab, without the highlighted one.
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => app.apply_pending(),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.search_query.push(c);
             app.adjust_selection();
         }
@@ -141,28 +177,93 @@ fn handle_value_selection_keys(key: event::KeyEvent, app: &mut App) {
     }
 }
 
+// Handle key presses while typing a custom value.
+fn handle_custom_input_keys(key: event::KeyEvent, app: &mut App) {
+    let Some(input) = app.custom.as_mut() else {
+        return;
+    };
+    match input.stage {
+        CustomStage::Value => match key.code {
+            KeyCode::Esc => app.custom = None,
+            KeyCode::Enter => app.advance_custom_input(),
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.revealed = app.allow_reveal && !input.revealed;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.value.push(c)
+            }
+            KeyCode::Backspace => {
+                input.value.expose_mut().pop();
+            }
+            _ => {}
+        },
+        CustomStage::SaveChoice => match key.code {
+            KeyCode::Esc => input.stage = CustomStage::Value,
+            KeyCode::Char('l') | KeyCode::Char('L') => app.choose_custom_save(Some(false)),
+            KeyCode::Char('g') | KeyCode::Char('G') => app.choose_custom_save(Some(true)),
+            KeyCode::Enter | KeyCode::Char('n') | KeyCode::Char('N') => {
+                app.choose_custom_save(None)
+            }
+            _ => {}
+        },
+        CustomStage::Label => match key.code {
+            KeyCode::Esc => input.stage = CustomStage::SaveChoice,
+            KeyCode::Enter => app.advance_custom_input(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.label.push(c)
+            }
+            KeyCode::Backspace => {
+                input.label.pop();
+            }
+            _ => {}
+        },
+        CustomStage::Done => {}
+    }
+}
+
 // Draw the UI widgets.
 fn draw(frame: &mut ratatui::Frame, app: &mut App) {
     let current_phase = app.current_phase;
     let i18n = app.i18n;
+    let pending = pending_summary(app);
 
     match current_phase {
         SelectionPhase::Variable => {
-            let (list_items, items_count) = {
-                let items = app.filtered_variables();
-                let count = items.len();
+            let list_items = {
+                let items = app.matched_variables();
                 let list_items: Vec<ListItem> = if items.is_empty() {
                     vec![ListItem::new(i18n.get("no_results")).italic()]
                 } else {
                     items
                         .iter()
-                        .map(|name| ListItem::new(name.to_string()))
+                        .map(|(name, m)| {
+                            let mut spans = highlight(name, 0, &m.positions, Style::default());
+                            // What the variable is set to now, as `show` prints it.
+                            spans.push(Span::styled(
+                                format!("  {}", app.variable_status(name).describe(i18n)),
+                                Style::default().fg(Color::DarkGray),
+                            ));
+                            if let Some(&index) = app.pending.get(name.as_str()) {
+                                let label = &app.config[name.as_str()].values[index].label;
+                                spans.push(Span::styled(
+                                    format!(" → {}", label),
+                                    Style::default().fg(Color::Yellow),
+                                ));
+                            }
+                            ListItem::new(Line::from(spans))
+                        })
                         .collect()
                 };
-                (list_items, count)
+                list_items
             };
             let title = i18n.get("select_variable");
-            let key_hint = i18n.get("key_hint_variable_selection");
+            let mut key_hint = i18n.get("key_hint_variable_selection").to_string();
+            if !app.pending.is_empty() {
+                key_hint = format!("{} | {}", key_hint, i18n.get("key_hint_apply_pending"));
+            }
+            if app.can_undo {
+                key_hint = format!("{} | {}", key_hint, i18n.get("key_hint_undo"));
+            }
             let list_widget = List::new(list_items)
                 .block(Block::default().title(title).borders(Borders::ALL))
                 .highlight_style(Style::default().bold().reversed())
@@ -172,43 +273,52 @@ fn draw(frame: &mut ratatui::Frame, app: &mut App) {
                 frame,
                 &app.search_query,
                 app.i18n,
-                items_count,
                 &mut app.variable_list_state,
                 list_widget,
-                key_hint,
+                &key_hint,
+                pending,
             );
+
+            if app.confirming {
+                draw_confirmation(frame, app);
+            } else if app.discarding {
+                draw_discard(frame, app);
+            }
         }
         SelectionPhase::Value => {
-            let (list_items, items_count) = {
-                let items = app.filtered_values();
-                let count = items.len();
-                let list_items: Vec<ListItem> = if items.is_empty() {
-                    vec![ListItem::new(i18n.get("no_results")).italic()]
-                } else {
-                    items
-                        .iter()
-                        .map(|v| {
-                            let label = &v.label;
-                            if label.starts_with("<Work> ") {
-                                ListItem::new(Line::from(vec![
-                                    Span::styled("<Work>", Style::default().fg(Color::Cyan).bold()),
-                                    Span::raw(label["<Work>".len()..].to_string()),
-                                ]))
-                            } else if label.starts_with("<Home> ") {
-                                ListItem::new(Line::from(vec![
-                                    Span::styled("<Home>", Style::default().fg(Color::Yellow).bold()),
-                                    Span::raw(label["<Home>".len()..].to_string()),
-                                ]))
-                            } else {
-                                ListItem::new(label.to_string())
-                            }
-                        })
-                        .collect()
-                };
-                (list_items, count)
+            let list_items = {
+                let items = app.matched_values();
+                let active = app.active_label();
+                let chosen = app.selected_variable.as_ref().and_then(|variable| {
+                    let index = *app.pending.get(variable)?;
+                    app.config.get(variable)?.values.get(index)
+                });
+                let mut list_items: Vec<ListItem> = items
+                    .iter()
+                    .map(|(v, m)| {
+                        let mark = if chosen.is_some_and(|c| std::ptr::eq(c, *v)) {
+                            Mark::Pending
+                        } else if active.as_deref() == Some(v.label.as_str()) {
+                            Mark::Active
+                        } else {
+                            Mark::None
+                        };
+                        value_list_item(v, &m.positions, mark, app.revealed_text(v), i18n)
+                    })
+                    .collect();
+                // Always last, so a value can be typed even when the search matches nothing.
+                list_items
+                    .push(ListItem::new(format!("  {}", i18n.get("custom_value_item"))).italic());
+                list_items
             };
             let title = i18n.get("select_value");
-            let key_hint = i18n.get("key_hint_value_selection");
+            let mut key_hint = i18n.get("key_hint_value_selection").to_string();
+            if !app.pending.is_empty() {
+                key_hint = format!("{} | {}", key_hint, i18n.get("key_hint_apply_pending"));
+            }
+            if app.allow_reveal {
+                key_hint = format!("{} | {}", key_hint, i18n.get("key_hint_reveal"));
+            }
             let list_widget = List::new(list_items)
                 .block(Block::default().title(title).borders(Borders::ALL))
                 .highlight_style(Style::default().bold().reversed())
@@ -218,33 +328,280 @@ fn draw(frame: &mut ratatui::Frame, app: &mut App) {
                 frame,
                 &app.search_query,
                 app.i18n,
-                items_count,
                 &mut app.value_list_state,
                 list_widget,
-                key_hint,
+                &key_hint,
+                pending,
             );
+
+            if app.confirming {
+                draw_confirmation(frame, app);
+            } else if app.custom.is_some() {
+                draw_custom_input(frame, app);
+            }
         }
     };
 }
 
+/// Draws the modal asking the user to confirm a value marked with `confirm`.
+fn draw_confirmation(frame: &mut ratatui::Frame, app: &App) {
+    let danger = Style::default().fg(Color::Red).bold();
+    let mut text = vec![Line::from(Span::styled(
+        app.i18n.get("confirm_title"),
+        danger,
+    ))];
+    // Every value about to be set that needs confirmation, including ones chosen with Tab,
+    // or the ones the undo restores.
+    let confirmations: Vec<(&str, &str)> = if app.undo_requested {
+        app.undo_confirmations
+            .iter()
+            .map(|(variable, label)| (variable.as_str(), label.as_str()))
+            .collect()
+    } else {
+        app.selections()
+            .into_iter()
+            .filter(|(_, value)| value.confirm)
+            .map(|(variable, value)| (variable, value.label.as_str()))
+            .collect()
+    };
+    for (variable, label) in confirmations {
+        text.push(Line::from(
+            app.i18n
+                .get("confirm_message")
+                .replace("{variable}", variable)
+                .replace("{label}", label),
+        ));
+    }
+    text.push(Line::from(""));
+    text.push(Line::from(app.i18n.get("key_hint_confirm")).centered());
+
+    let area = centered_rect(60, text.len() as u16 + 2, frame.area());
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).border_style(danger));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Draws the modal asking whether to quit without setting the values chosen with Tab.
+fn draw_discard(frame: &mut ratatui::Frame, app: &App) {
+    let mut text = vec![Line::from(app.i18n.get("discard_title")).bold()];
+    text.extend(pending_summary(app));
+    text.push(Line::from(""));
+    text.push(Line::from(app.i18n.get("key_hint_discard")).centered());
+
+    let area = centered_rect(60, text.len() as u16 + 2, frame.area());
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Draws the modal for typing a value that isn't in the config, and for saving it.
+fn draw_custom_input(frame: &mut ratatui::Frame, app: &App) {
+    let (Some(input), Some(variable)) = (&app.custom, &app.selected_variable) else {
+        return;
+    };
+    let i18n = app.i18n;
+    // The value is masked unless the user chose to reveal it, and borrowed when it is.
+    let shown = if input.revealed {
+        Span::raw(input.value.expose())
+    } else {
+        Span::raw("•".repeat(input.value.expose().chars().count()))
+    };
+    let mut text = vec![
+        Line::from(
+            i18n.get("custom_value_title")
+                .replace("{variable}", variable),
+        )
+        .bold(),
+        Line::from(vec![Span::raw("> "), shown]),
+        Line::from(""),
+    ];
+    match input.stage {
+        CustomStage::Value => {
+            let mut key_hint = i18n.get("key_hint_custom_value").to_string();
+            if app.allow_reveal {
+                key_hint = format!("{} | {}", i18n.get("key_hint_custom_reveal"), key_hint);
+            }
+            text.push(Line::from(key_hint).centered());
+        }
+        CustomStage::SaveChoice | CustomStage::Done => {
+            text.push(Line::from(i18n.get("custom_save_prompt")));
+            text.push(Line::from(""));
+            text.push(Line::from(i18n.get("key_hint_custom_save")).centered());
+        }
+        CustomStage::Label => {
+            let file = if input.save_global == Some(true) {
+                i18n.get("custom_save_global")
+            } else {
+                i18n.get("custom_save_local")
+            };
+            text.push(Line::from(file));
+            text.push(Line::from(format!(
+                "{}{}",
+                i18n.get("custom_label_prompt"),
+                input.label
+            )));
+            text.push(Line::from(""));
+            text.push(Line::from(i18n.get("key_hint_custom_label")).centered());
+        }
+    }
+
+    let area = centered_rect(60, text.len() as u16 + 2, frame.area());
+    let paragraph = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Lists the values chosen with Tab, one `VARIABLE = label` line each.
+fn pending_summary(app: &App) -> Vec<Line<'static>> {
+    app.pending_values()
+        .into_iter()
+        .map(|(variable, value)| Line::from(format!("{} = {}", variable, value.label)))
+        .collect()
+}
+
+/// How a value is flagged in the value list.
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    /// The variable holds this value now.
+    Active,
+    /// This value was chosen with Tab and will be set.
+    Pending,
+    None,
+}
+
+/// Builds the list entry for a value: a mark if it is active or chosen, its label with a colored
+/// origin prefix (in red if it needs confirmation), the value itself while it is revealed, and the
+/// other variables it sets, if any.
+fn value_list_item<'a>(
+    value: &EnvValue,
+    positions: &[usize],
+    mark: Mark,
+    revealed: Option<Revealed<'a>>,
+    i18n: &crate::i18n::I18nMessages,
+) -> ListItem<'a> {
+    let label = &value.label;
+    // Values that need confirmation stand out in red.
+    let label_style = if value.confirm {
+        Style::default().fg(Color::Red).bold()
+    } else {
+        Style::default()
+    };
+    let prefix = [
+        (WORK_PREFIX, "<Work>", Color::Cyan),
+        (HOME_PREFIX, "<Home>", Color::Yellow),
+    ]
+    .into_iter()
+    .find_map(|(prefix, tag, color)| Some((label.strip_prefix(prefix)?, tag, color)));
+    // Marked values are flagged in front of the label; the others are indented to line up.
+    let mut spans = vec![match mark {
+        Mark::Active => Span::styled("✓ ", Style::default().fg(Color::Green).bold()),
+        Mark::Pending => Span::styled("● ", Style::default().fg(Color::Yellow).bold()),
+        Mark::None => Span::raw("  "),
+    }];
+    spans.extend(if let Some((rest, tag, color)) = prefix {
+        let tag_len = tag.chars().count();
+        let mut spans = highlight(tag, 0, positions, Style::default().fg(color).bold());
+        // `positions` index into the whole label, including the prefix and the space after it.
+        spans.extend(highlight(
+            &format!(" {}", rest),
+            tag_len,
+            positions,
+            label_style,
+        ));
+        spans
+    } else {
+        highlight(label, 0, positions, label_style)
+    });
+
+    // The revealed text is borrowed rather than formatted, so it isn't copied into a string.
+    let reveal_style = Style::default().fg(Color::Magenta);
+    match revealed {
+        Some(Revealed::Full(text)) => {
+            spans.push(Span::styled("  = ", reveal_style));
+            spans.push(Span::styled(text, reveal_style));
+        }
+        Some(Revealed::Tail(text)) => {
+            spans.push(Span::styled("  = …", reveal_style));
+            spans.push(Span::styled(text, reveal_style));
+        }
+        Some(Revealed::NotInline) => spans.push(Span::styled(
+            format!("  = {}", i18n.get("reveal_not_inline")),
+            reveal_style,
+        )),
+        None => {}
+    }
+    if !value.also.is_empty() {
+        let variables: Vec<&str> = value.also.keys().map(String::as_str).collect();
+        spans.push(Span::styled(
+            i18n.get("also_sets")
+                .replace("{variables}", &variables.join(", ")),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+/// Splits `text` into spans, underlining the characters matched by the search query.
+///
+/// `positions` are character indices into a longer string that `text` starts at `offset` of.
+fn highlight(text: &str, offset: usize, positions: &[usize], style: Style) -> Vec<Span<'static>> {
+    let matched = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let is_matched = positions.contains(&(offset + i));
+        if is_matched != run_matched && !run.is_empty() {
+            let run_style = if run_matched { matched } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_matched = is_matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        let run_style = if run_matched { matched } else { style };
+        spans.push(Span::styled(run, run_style));
+    }
+    spans
+}
+
 /// Helper function to render the common layout.
 fn render_layout(
     frame: &mut ratatui::Frame,
     search_query: &str,
     i18n: &crate::i18n::I18nMessages,
-    items_count: usize,
     state: &mut ratatui::widgets::ListState,
     list_widget: List,
     key_hint: &str,
+    pending: Vec<Line>,
 ) {
     // Height calculation:
     // Search Box: 3 lines (border top + text + border bottom)
     // List Borders: 2 lines (top + bottom)
-    // Key Hint (Footer): 1 line
+    // Key Hint (Footer): 1 line, or more if it doesn't fit
     // Total margin: 6 lines
-    let list_height = if items_count == 0 { 1 } else { items_count as u16 };
+    let key_hint = wrap_key_hint(key_hint, (frame.area().width * 80 / 100) as usize);
+    let footer_height = key_hint.len() as u16;
+    let items_count = list_widget.len();
+    let list_height = if items_count == 0 {
+        1
+    } else {
+        items_count as u16
+    };
+    // The summary of values chosen with Tab: one line each, plus its borders.
+    let summary_height = if pending.is_empty() {
+        0
+    } else {
+        pending.len() as u16 + 2
+    };
     let max_height = frame.area().height * 80 / 100;
-    let total_height = (list_height + 6).min(max_height);
+    let total_height = (list_height + 5 + footer_height + summary_height).min(max_height);
     let area = centered_rect(80, total_height, frame.area());
 
     let chunks = Layout::default()
@@ -252,27 +609,54 @@ fn render_layout(
         .constraints([
             Constraint::Length(3),
             Constraint::Min(0),
-            Constraint::Length(1),
+            Constraint::Length(summary_height),
+            Constraint::Length(footer_height),
         ])
         .split(area);
 
     let search_area = chunks[0];
     let main_area = chunks[1];
-    let footer_area = chunks[2];
+    let summary_area = chunks[2];
+    let footer_area = chunks[3];
 
     // Search Box
     let search_text = format!("{}{}", i18n.get("search_placeholder"), search_query);
-    let search_box = Paragraph::new(search_text)
-        .block(Block::default().borders(Borders::ALL).title("Search"));
+    let search_box =
+        Paragraph::new(search_text).block(Block::default().borders(Borders::ALL).title("Search"));
 
-    let key_hint_paragraph = Paragraph::new(Line::from(key_hint).centered());
+    let key_hint_paragraph = Paragraph::new(key_hint).centered();
 
     frame.render_widget(Clear, area);
     frame.render_widget(search_box, search_area);
     frame.render_stateful_widget(list_widget, main_area, state);
+    if !pending.is_empty() {
+        let summary = Paragraph::new(pending).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(i18n.get("pending_title")),
+        );
+        frame.render_widget(summary, summary_area);
+    }
     frame.render_widget(key_hint_paragraph, footer_area);
 }
 
+/// Splits a key hint into lines of at most `width` columns, breaking only between its
+/// `|`-separated entries.
+fn wrap_key_hint(key_hint: &str, width: usize) -> Vec<Line<'static>> {
+    let mut lines: Vec<String> = Vec::new();
+    for entry in key_hint.split(" | ") {
+        match lines.last_mut() {
+            Some(line) if Line::from(format!("{} | {}", line, entry)).width() <= width => {
+                line.push_str(" | ");
+                line.push_str(entry);
+            }
+            _ => lines.push(entry.to_string()),
+        }
+    }
+    lines.into_iter().map(Line::from).collect()
+}
+
 /// Helper function to create a centered rect.
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     // Ensure that the height does not exceed the available area's height.
@@ -0,0 +1,434 @@
+//! Remembers what variables held before gnb-envswap changed them.
+//!
+//! Every emitted change also sets `GNB_ENVSWAP_UNDO`, a JSON stack that tells,
+//! for each changed variable, whether it was set before and which configured
+//! value (label and source file) it held. Values are never put in the stack,
+//! since every child process inherits it; `gnb-envswap undo` pops the latest
+//! entry and resolves the configured values again.
+//!
+//! A previous value that isn't in any config is kept in a file under the
+//! user's data directory instead, readable by its owner only, and deleted once
+//! it is restored or dropped from the stack. The file is encrypted with a
+//! random age key that is only put in the stack, so it can't be read once
+//! the shell session that holds the stack is gone.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use age::secrecy::ExposeSecret;
+use age::{Decryptor, Encryptor, Identity, Recipient};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::config::Config;
+use crate::datadir::{self, open_private};
+use crate::secret::Secret;
+use crate::state::SessionState;
+
+/// The environment variable holding the undo stack.
+pub const UNDO_VARIABLE: &str = "GNB_ENVSWAP_UNDO";
+
+/// How many changes can be undone. Older ones are forgotten.
+const MAX_LEVELS: usize = 10;
+
+/// How long files of kept values are left around before they are deleted.
+///
+/// They can't be decrypted without the key in the stack, so this only keeps
+/// the files of closed shell sessions from piling up.
+const KEEP_FOR: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// What a variable held before a change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Previous {
+    /// The variable was not set.
+    #[serde(rename = "n")]
+    NotSet,
+    /// The variable held a configured value.
+    #[serde(rename = "c")]
+    Configured(ConfiguredValue),
+    /// The variable held a value that isn't in any config, kept in a file.
+    #[serde(rename = "k")]
+    Kept,
+}
+
+/// Identifies a configured value without revealing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfiguredValue {
+    #[serde(rename = "l")]
+    pub label: String,
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// For variables set through another value's `also` table, the variable that was selected.
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+}
+
+/// What the variables changed at once held before.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    #[serde(rename = "p")]
+    pub previous: BTreeMap<String, Previous>,
+    /// The session state before the change, which holds no values.
+    #[serde(rename = "st", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// The name of the file keeping the [`Previous::Kept`] values.
+    #[serde(rename = "f", default, skip_serializing_if = "Option::is_none")]
+    pub kept: Option<String>,
+    /// The age identity the kept file is encrypted with, which is never written to disk.
+    #[serde(rename = "k", default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// The changes that can be undone, oldest first.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    /// Reads the stack from `GNB_ENVSWAP_UNDO`. A missing or malformed stack is empty.
+    pub fn from_env() -> Self {
+        std::env::var(UNDO_VARIABLE)
+            .map(|stack| Self::parse(&stack))
+            .unwrap_or_default()
+    }
+
+    /// Parses a serialized stack. A malformed stack is treated as empty.
+    pub fn parse(stack: &str) -> Self {
+        UndoStack {
+            entries: serde_json::from_str(stack).unwrap_or_default(),
+        }
+    }
+
+    /// Serializes the stack for `GNB_ENVSWAP_UNDO`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.entries).expect("undo entries are always serializable")
+    }
+
+    /// Returns whether there is nothing to undo.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remembers a change, forgetting and returning the oldest change if the stack is full.
+    pub fn push(&mut self, entry: UndoEntry) -> Option<UndoEntry> {
+        let dropped = (self.entries.len() == MAX_LEVELS).then(|| self.entries.remove(0));
+        self.entries.push(entry);
+        dropped
+    }
+
+    /// Returns the latest change without removing it.
+    pub fn latest(&self) -> Option<&UndoEntry> {
+        self.entries.last()
+    }
+
+    /// Removes and returns the latest change.
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop()
+    }
+}
+
+/// Works out what `variables`, read with `env`, held before they are changed.
+///
+/// A value is identified through the session state, or else by matching the
+/// inline values of the config. The values that can't be identified are
+/// returned separately, to be kept with [`KeptValues::keep`].
+pub fn capture<'a, I, F>(
+    variables: I,
+    env: F,
+    state: &SessionState,
+    config: &Config,
+) -> (UndoEntry, BTreeMap<String, Secret>)
+where
+    I: IntoIterator<Item = &'a str>,
+    F: Fn(&str) -> Option<String>,
+{
+    let mut entry = UndoEntry {
+        state: env(crate::state::STATE_VARIABLE),
+        ..Default::default()
+    };
+    let mut kept = BTreeMap::new();
+    for variable in variables {
+        let Some(value) = env(variable).map(Secret::from) else {
+            entry
+                .previous
+                .insert(variable.to_string(), Previous::NotSet);
+            continue;
+        };
+        let configured = match state.entry(variable, value.expose()) {
+            Some(state_entry) => Some(ConfiguredValue {
+                label: state_entry.label.clone(),
+                source: state_entry.source.clone(),
+                via: state_entry.via.clone(),
+            }),
+            None => config.get(variable).and_then(|var| {
+                var.values
+                    .iter()
+                    .find(|v| {
+                        v.value
+                            .as_ref()
+                            .is_some_and(|v| v.expose() == value.expose())
+                    })
+                    .map(|v| ConfiguredValue {
+                        label: v.label.clone(),
+                        source: v.source.as_ref().map(|p| p.display().to_string()),
+                        via: None,
+                    })
+            }),
+        };
+        let previous = match configured {
+            Some(configured) => Previous::Configured(configured),
+            None => {
+                kept.insert(variable.to_string(), value);
+                Previous::Kept
+            }
+        };
+        entry.previous.insert(variable.to_string(), previous);
+    }
+    (entry, kept)
+}
+
+/// The files keeping previous values that aren't in any config.
+pub struct KeptValues {
+    dir: PathBuf,
+}
+
+impl KeptValues {
+    /// Opens the directory for kept values in the user's data directory.
+    pub fn open() -> Result<Self, String> {
+        Ok(Self::open_in(datadir::path()?.join("undo")))
+    }
+
+    /// Opens the kept values in `dir`, which may not exist yet.
+    fn open_in(dir: PathBuf) -> Self {
+        KeptValues { dir }
+    }
+
+    /// Encrypts `values` to a new file with a new key, and returns the file name and the key.
+    ///
+    /// Files left behind by closed shell sessions are deleted along the way.
+    pub fn keep(&self, values: &BTreeMap<String, Secret>) -> Result<(String, String), String> {
+        self.remove_stale();
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public();
+        let encryptor = Encryptor::with_recipients(std::iter::once(&recipient as &dyn Recipient))
+            .map_err(|e| format!("Failed to encrypt the values to undo: {}", e))?;
+        let content = Secret::from(
+            serde_json::to_string(values).expect("kept values are always serializable"),
+        );
+        let mut encrypted = Vec::new();
+        let result = encryptor
+            .wrap_output(&mut encrypted)
+            .and_then(|mut writer| {
+                writer.write_all(content.expose().as_bytes())?;
+                writer.finish()
+            });
+        result.map_err(|e| format!("Failed to encrypt the values to undo: {}", e))?;
+
+        let name = datadir::random_hex(16)?;
+        let path = self.dir.join(&name);
+        let mut file = open_private(&path, OpenOptions::new().write(true).create_new(true))?;
+        file.write_all(&encrypted)
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        let key = identity.to_string().expose_secret().to_string();
+        Ok((name, key))
+    }
+
+    /// Reads the values kept in the file `name` with `key`, or `None` if the file is gone.
+    pub fn read(&self, name: &str, key: &str) -> Result<Option<BTreeMap<String, Secret>>, String> {
+        let Some(path) = self.path(name) else {
+            return Ok(None);
+        };
+        let encrypted = match fs::read(&path) {
+            Ok(encrypted) => encrypted,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+        let identity: age::x25519::Identity = key
+            .parse()
+            .map_err(|e| format!("Invalid key in {}: {}", UNDO_VARIABLE, e))?;
+        let mut reader = Decryptor::new_buffered(encrypted.as_slice())
+            .and_then(|decryptor| decryptor.decrypt(std::iter::once(&identity as &dyn Identity)))
+            .map_err(|e| format!("Failed to decrypt {:?}: {}", path, e))?;
+        // The plaintext is shorter than the ciphertext, so the buffer is never reallocated.
+        let mut content = Zeroizing::new(Vec::with_capacity(encrypted.len()));
+        reader
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Failed to decrypt {:?}: {}", path, e))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+    }
+
+    /// Deletes the file `name`, if it is still there.
+    pub fn forget(&self, name: &str) -> Result<(), String> {
+        let Some(path) = self.path(name) else {
+            return Ok(());
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to delete {:?}: {}", path, e)),
+        }
+    }
+
+    /// Returns the path of the file `name`, unless the name isn't one we made.
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let valid = !name.is_empty() && name.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| self.dir.join(name))
+    }
+
+    /// Deletes the files that are older than [`KEEP_FOR`].
+    fn remove_stale(&self) {
+        let Ok(files) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for file in files.flatten() {
+            let stale = file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > KEEP_FOR));
+            if stale {
+                let _ = fs::remove_file(file.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EnvValue, EnvVar};
+    use std::collections::HashMap;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "API_KEY" => Some("it's-dev".to_string()),
+            "TOKEN" => Some("typed".to_string()),
+            "API_URL" => Some("from-command".to_string()),
+            _ => None,
+        }
+    }
+
+    fn config() -> Config {
+        let mut config = HashMap::new();
+        config.insert(
+            "API_KEY".to_string(),
+            EnvVar {
+                values: vec![EnvValue {
+                    label: "Dev".to_string(),
+                    value: Some("it's-dev".into()),
+                    ..Default::default()
+                }],
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn test_capture_keeps_values_out_of_the_stack() {
+        let mut state = SessionState::parse("", "salt".to_string());
+        state.record("API_URL", "Prod", None, Some("API_KEY"), "from-command");
+
+        let (entry, kept) = capture(
+            ["API_KEY", "API_URL", "TOKEN", "UNSET"],
+            env,
+            &state,
+            &config(),
+        );
+        assert_eq!(
+            entry.previous["API_KEY"],
+            Previous::Configured(ConfiguredValue {
+                label: "Dev".to_string(),
+                source: None,
+                via: None,
+            })
+        );
+        assert_eq!(
+            entry.previous["API_URL"],
+            Previous::Configured(ConfiguredValue {
+                label: "Prod".to_string(),
+                source: None,
+                via: Some("API_KEY".to_string()),
+            })
+        );
+        assert_eq!(entry.previous["TOKEN"], Previous::Kept);
+        assert_eq!(entry.previous["UNSET"], Previous::NotSet);
+        assert_eq!(kept.keys().collect::<Vec<_>>(), ["TOKEN"]);
+        assert_eq!(kept["TOKEN"].expose(), "typed");
+
+        let mut stack = UndoStack::default();
+        stack.push(entry.clone());
+        let json = stack.to_json();
+        assert!(!json.contains("it's-dev"));
+        assert!(!json.contains("typed"));
+        assert!(!json.contains("from-command"));
+
+        let mut stack = UndoStack::parse(&json);
+        assert_eq!(stack.latest(), Some(&entry));
+        assert_eq!(stack.pop().unwrap(), entry);
+        assert!(stack.is_empty());
+        assert!(UndoStack::parse("not json").is_empty());
+    }
+
+    #[test]
+    fn test_multiple_levels_are_capped() {
+        let mut stack = UndoStack::default();
+        for i in 0..MAX_LEVELS + 2 {
+            let entry = UndoEntry {
+                kept: Some(i.to_string()),
+                ..Default::default()
+            };
+            let dropped = stack.push(entry);
+            assert_eq!(
+                dropped.and_then(|entry| entry.kept),
+                i.checked_sub(MAX_LEVELS).map(|i| i.to_string())
+            );
+        }
+
+        let popped: Vec<String> = std::iter::from_fn(|| stack.pop())
+            .map(|entry| entry.kept.unwrap())
+            .collect();
+        assert_eq!(popped.len(), MAX_LEVELS);
+        assert_eq!(popped.first().unwrap(), &(MAX_LEVELS + 1).to_string());
+        assert_eq!(popped.last().unwrap(), "2");
+    }
+
+    #[test]
+    fn test_kept_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = KeptValues::open_in(dir.path().join("undo"));
+        let mut values = BTreeMap::new();
+        values.insert("TOKEN".to_string(), Secret::from("typed"));
+
+        let (name, key) = kept.keep(&values).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.path().join("undo").join(&name))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let encrypted = fs::read(dir.path().join("undo").join(&name)).unwrap();
+        assert!(!String::from_utf8_lossy(&encrypted).contains("typed"));
+        let read = kept.read(&name, &key).unwrap().unwrap();
+        assert_eq!(read["TOKEN"].expose(), "typed");
+
+        // Another session's key can't read the file.
+        let (_, other_key) = kept.keep(&values).unwrap();
+        assert!(kept.read(&name, &other_key).is_err());
+
+        kept.forget(&name).unwrap();
+        assert!(kept.read(&name, &key).unwrap().is_none());
+        kept.forget(&name).unwrap();
+
+        // Names from a tampered stack can't point outside the directory.
+        assert!(kept.read("../salt", &key).unwrap().is_none());
+    }
+}